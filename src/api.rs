@@ -0,0 +1,387 @@
+use crate::backtest_runner::{get_run_artefacts, RunOutput};
+use crate::jobs::*;
+use crate::params::*;
+use crate::plan::{plan_dir, Plan};
+use crate::results::best::*;
+use crate::results::csv_reader::{read_csv_rows, read_results_csv};
+use crate::results::query::{query_rows, InvalidQuery, RowQuery};
+use crate::results::robustness::*;
+
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_files::{file_extension_to_mime, NamedFile};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound},
+    http::header,
+    web, Error as ActixError, HttpRequest, HttpResponse,
+};
+use actix_web_actors::ws;
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::StreamExt;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobSubmitted {
+    pub id: JobId,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResultsQuery {
+    // return the parsed rows instead of the paths to the csv files
+    #[serde(default)]
+    pub rows: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum JobResults {
+    // the merged csv and the csv files and diagnostics of the individual runs of the queue
+    Files { csv: PathBuf, runs: Vec<RunOutput> },
+    Rows(Vec<serde_json::Map<String, serde_json::Value>>),
+}
+
+// A run is accepted with nested indicators or with paths to indicator files like a run config.
+// Indicator files that can't be read and invalid runs are a validation error.
+pub fn parse_run(value: serde_json::Value) -> Result<RunParams, ActixError> {
+    let run = match serde_json::from_value::<RunParams>(value.clone()) {
+        Ok(run) => run,
+        Err(e) => match serde_json::from_value::<RunParamsFile>(value) {
+            Ok(file) => RunParams::try_from(file).map_err(ErrorBadRequest)?,
+            Err(_) => return Err(ErrorBadRequest(e)),
+        },
+    };
+    run.validate().map_err(ErrorBadRequest)?;
+    Ok(run)
+}
+
+pub async fn submit_job(
+    data: web::Json<serde_json::Value>,
+    config: web::Data<CommonParams>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let run = parse_run(data.into_inner())?;
+    info!("submitting job run:{:?}", run);
+    if run.walk_forward.is_some() {
+        return Err(ErrorBadRequest(
+            "walk-forward runs are only supported by the CLI",
+        ));
+    }
+
+    let name = run.name.clone();
+    let runs = run
+        .split_run_into_queue(&config.split)
+        .map_err(|e| ErrorBadRequest(format!("{:#}", e)))?;
+    let id = jobs.submit(name, runs).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Accepted().json(JobSubmitted { id }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlanQuery {
+    // also write the files of every run to <reports>/<name>_plan
+    #[serde(default)]
+    pub write: bool,
+}
+
+// the queue a run would be split into with the files of every run, without running it
+pub async fn plan_run(
+    data: web::Json<serde_json::Value>,
+    query: web::Query<PlanQuery>,
+    config: web::Data<CommonParams>,
+) -> Result<HttpResponse, ActixError> {
+    let run = parse_run(data.into_inner())?;
+    let plan = Plan::new(&config, run).map_err(|e| ErrorBadRequest(format!("{:#}", e)))?;
+    if query.write {
+        let reports = get_reports_dir(&config).map_err(ErrorInternalServerError)?;
+        plan.write(&plan_dir(&reports, &plan), &config)
+            .map_err(ErrorInternalServerError)?;
+    }
+    Ok(HttpResponse::Ok().json(plan))
+}
+
+pub async fn job_status(
+    id: web::Path<JobId>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let id = id.into_inner();
+    let status = jobs
+        .status(id)
+        .ok_or_else(|| ErrorNotFound(format!("job {} not found", id)))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// sends the events of a job as json text messages and closes the socket when the job is finished
+struct JobEventsSocket {
+    events: Option<UnboundedReceiver<JobEvent>>,
+}
+
+impl Actor for JobEventsSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(events) = self.events.take() {
+            ctx.add_stream(events);
+        }
+    }
+}
+
+impl StreamHandler<JobEvent> for JobEventsSocket {
+    fn handle(&mut self, event: JobEvent, ctx: &mut Self::Context) {
+        match serde_json::to_string(&event) {
+            Ok(text) => ctx.text(text),
+            Err(e) => error!("serializing {:?} failed: {}", event, e),
+        }
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseCode::Normal.into()));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for JobEventsSocket {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(e) => {
+                warn!("job events socket failed: {}", e);
+                ctx.stop();
+            }
+            _ => (),
+        }
+    }
+}
+
+// The progress of a job as a WebSocket or, if the request is no WebSocket upgrade, as
+// server-sent events. Both end when the job is finished.
+pub async fn job_events(
+    req: HttpRequest,
+    payload: web::Payload,
+    id: web::Path<JobId>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let id = id.into_inner();
+    let events = jobs
+        .subscribe(id)
+        .ok_or_else(|| ErrorNotFound(format!("job {} not found", id)))?;
+
+    let upgrade = req
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.eq_ignore_ascii_case("websocket"));
+    if upgrade {
+        return ws::start(
+            JobEventsSocket {
+                events: Some(events),
+            },
+            &req,
+            payload,
+        );
+    }
+
+    let body = events.map(|event| {
+        serde_json::to_string(&event)
+            .map(|json| web::Bytes::from(format!("data: {}\n\n", json)))
+            .map_err(ErrorInternalServerError)
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .streaming(body))
+}
+
+// cancels a queued or running job. The terminal of a running job is killed
+pub async fn cancel_job(
+    id: web::Path<JobId>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let id = id.into_inner();
+    match jobs.cancel(id) {
+        None => Err(ErrorNotFound(format!("job {} not found", id))),
+        Some(false) => Err(ErrorConflict(format!("job {} is already finished", id))),
+        Some(true) => Ok(HttpResponse::Accepted().json(jobs.status(id))),
+    }
+}
+
+// the job with its merged results or an error response if it's not done
+fn finished_job(jobs: &JobQueue, id: JobId) -> Result<(Job, PathBuf), ActixError> {
+    let job = jobs
+        .get(id)
+        .ok_or_else(|| ErrorNotFound(format!("job {} not found", id)))?;
+
+    match job.state {
+        JobState::Done => (),
+        JobState::Failed => {
+            return Err(ErrorConflict(format!(
+                "job {} failed: {}",
+                id,
+                job.error.unwrap_or_default()
+            )))
+        }
+        s => return Err(ErrorConflict(format!("job {} is not done: {:?}", id, s))),
+    }
+
+    let merged = job
+        .merged
+        .clone()
+        .ok_or_else(|| ErrorInternalServerError(format!("job {} has no results", id)))?;
+    Ok((job, merged))
+}
+
+pub async fn job_results(
+    id: web::Path<JobId>,
+    query: web::Query<ResultsQuery>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let (job, merged) = finished_job(&jobs, id.into_inner())?;
+    if !query.rows {
+        return Ok(HttpResponse::Ok().json(JobResults::Files {
+            csv: merged,
+            runs: job.results,
+        }));
+    }
+
+    let rows = read_csv_rows(&merged).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(JobResults::Rows(rows)))
+}
+
+// the indicator set the job was submitted with. The runs of a sliced job only cover a part of the
+// ranges
+fn job_indicator_set(job: &Job) -> Result<IndicatorSet, ActixError> {
+    IndicatorSet::merge_ranges(&job.runs.iter().map(|r| &r.indi_set).collect::<Vec<_>>())
+        .ok_or_else(|| ErrorInternalServerError(format!("job {} has no runs", job.id)))
+}
+
+// the best passes of a job with the indicators ready to be saved as config
+pub async fn job_best(
+    id: web::Path<JobId>,
+    query: web::Query<RankParams>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let (job, merged) = finished_job(&jobs, id.into_inner())?;
+    let (header, rows) = read_results_csv(&merged).map_err(ErrorInternalServerError)?;
+    let base = job_indicator_set(&job)?;
+    let best = best_passes(&header, &rows, &base, &query).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(best))
+}
+
+// the passes of a job ranked by the metric over their neighbours in parameter space
+pub async fn job_robustness(
+    id: web::Path<JobId>,
+    query: web::Query<RobustnessParams>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let (job, merged) = finished_job(&jobs, id.into_inner())?;
+    let (header, rows) = read_results_csv(&merged).map_err(ErrorInternalServerError)?;
+    let base = job_indicator_set(&job)?;
+    let scores =
+        score_robustness(&header, &rows, &base, &query).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(scores))
+}
+
+// A page of the rows of <reports>/<run>.csv or, if there is no csv, of the report
+// <reports>/<run>.xml. Every run of a queue and the merged results of a job can be queried. The
+// merged results are in the reports of the config, the runs in those of the terminal instance
+// that executed them.
+pub async fn query_results(
+    run: web::Path<String>,
+    query: web::Query<RowQuery>,
+    config: web::Data<CommonParams>,
+) -> Result<HttpResponse, ActixError> {
+    let run = run.into_inner();
+    if run.is_empty() || run.contains(['/', '\\']) || run.contains("..") {
+        return Err(ErrorBadRequest(format!("invalid run name: {}", run)));
+    }
+    let mut dirs = vec![get_reports_dir(&config).map_err(ErrorInternalServerError)?];
+    for instance in config.instances() {
+        dirs.push(get_reports_dir(&instance).map_err(ErrorInternalServerError)?);
+    }
+    let file = ["csv", "xml"]
+        .iter()
+        .map(|ext| format!("{}.{}", run, ext))
+        .flat_map(|name| dirs.iter().map(move |d| d.join(&name)))
+        .find(|f| f.exists())
+        .ok_or_else(|| ErrorNotFound(format!("no results of {}", run)))?;
+
+    let page = query_rows(&file, &query).map_err(|e| match e.downcast_ref::<InvalidQuery>() {
+        Some(_) => ErrorBadRequest(e.to_string()),
+        None => ErrorInternalServerError(format!("{:#}", e)),
+    })?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+// a file of a job that can be downloaded from /jobs/{id}/files/{file}
+#[derive(Debug, Serialize)]
+pub struct Artefact {
+    // the index of the run in the queue, None for the merged results of the job
+    pub run: Option<usize>,
+    pub file: String,
+    pub size: u64,
+}
+
+// the csv, tester log, terminal.ini and .set file of every run and the merged csv
+fn job_artefacts(config: &CommonParams, job: &Job) -> Result<Vec<(Artefact, PathBuf)>, ActixError> {
+    let mut files = Vec::new();
+    for (i, run) in job.runs.iter().enumerate() {
+        let artefacts = get_run_artefacts(config, run).map_err(ErrorInternalServerError)?;
+        files.extend(artefacts.into_iter().map(|f| (Some(i), f)));
+    }
+    files.extend(job.merged.iter().map(|f| (None, f.clone())));
+
+    Ok(files
+        .into_iter()
+        .filter_map(|(run, path)| {
+            let artefact = Artefact {
+                run,
+                file: path.file_name()?.to_string_lossy().into_owned(),
+                size: path.metadata().ok()?.len(),
+            };
+            Some((artefact, path))
+        })
+        .collect())
+}
+
+pub async fn job_files(
+    id: web::Path<JobId>,
+    config: web::Data<CommonParams>,
+    jobs: web::Data<JobQueue>,
+) -> Result<HttpResponse, ActixError> {
+    let id = id.into_inner();
+    let job = jobs
+        .get(id)
+        .ok_or_else(|| ErrorNotFound(format!("job {} not found", id)))?;
+    let artefacts = job_artefacts(&config, &job)?
+        .into_iter()
+        .map(|(a, _)| a)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(artefacts))
+}
+
+// A single artefact of a job with range requests. Only the files of the job can be downloaded.
+pub async fn job_file(
+    path: web::Path<(JobId, String)>,
+    config: web::Data<CommonParams>,
+    jobs: web::Data<JobQueue>,
+) -> Result<NamedFile, ActixError> {
+    let (id, file) = path.into_inner();
+    let job = jobs
+        .get(id)
+        .ok_or_else(|| ErrorNotFound(format!("job {} not found", id)))?;
+    let (_, path) = job_artefacts(&config, &job)?
+        .into_iter()
+        .find(|(a, _)| a.file == file)
+        .ok_or_else(|| ErrorNotFound(format!("job {} has no file {}", id, file)))?;
+
+    let named = NamedFile::open(&path)?;
+    // the tester log, terminal.ini and .set file are text but unknown to mime_guess
+    Ok(match path.extension().and_then(|e| e.to_str()) {
+        Some("log") | Some("ini") | Some("set") => {
+            named.set_content_type(file_extension_to_mime("txt"))
+        }
+        _ => named,
+    })
+}
@@ -1,16 +1,75 @@
 use super::params::*;
+use crate::results::merge::merge_csv_results;
+use crate::results::tester_log::*;
 use crate::results::xml_reader::*;
 use crate::results::ResultRow;
 
 use anyhow::{Context, Result};
 use chrono::prelude::*;
+use derive_more::Display;
+use std::collections::VecDeque;
 use std::fs::{self, File};
 use std::io::prelude::*;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::{Command, ExitStatus};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{thread, time};
 
+// poll interval of the terminal process and its tester log
+const SUPERVISE_INTERVAL: time::Duration = time::Duration::from_millis(250);
+
+#[derive(Debug, PartialEq, Copy, Clone, Display)]
+pub enum TimeoutKind {
+    #[display(fmt = "run")]
+    Run,
+    #[display(fmt = "inactivity")]
+    Inactivity,
+}
+
+// the terminal was killed after a timeout. Contains the tester log up to that point
+#[derive(Debug, Display)]
+#[display(
+    fmt = "terminal killed after {} timeout of {}s\nTester log:\n{}",
+    kind,
+    secs,
+    log
+)]
+pub struct TerminalTimeout {
+    pub kind: TimeoutKind,
+    pub secs: u64,
+    pub log: String,
+}
+
+impl std::error::Error for TerminalTimeout {}
+
+// Cancels a queue. The terminal of a running run is killed and the runs that didn't start yet are
+// skipped. Clones share the state.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// the queue was stopped with a CancelToken
+#[derive(Debug, Display)]
+#[display(fmt = "cancelled")]
+pub struct Cancelled;
+
+impl std::error::Error for Cancelled {}
+
+pub fn is_cancelled(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<Cancelled>().is_some()
+}
+
 #[derive(Debug)]
 pub struct BacktestRunner {
     common: CommonParams,
@@ -32,6 +91,11 @@ impl BacktestRunner {
         Ok(())
     }
 
+    fn delete_indi_params(&self) -> Result<()> {
+        let params = self.common.params_path();
+        fs::remove_file(&params).context(format!("removing {:?} failed", params))
+    }
+
     fn write_terminal_config(&self) -> Result<()> {
         debug!("writing {:?}", self.common.params_path());
         let mut file = File::create(self.common.workdir.join("terminal.ini").as_path())?;
@@ -39,10 +103,14 @@ impl BacktestRunner {
         Ok(())
     }
 
-    pub fn run(&self) -> Result<ExitStatus> {
+    // on_log gets the lines of the tester log as they are written
+    pub fn run(&self, cancel: &CancelToken, on_log: &dyn Fn(&str)) -> Result<ExitStatus> {
         let mut cmd: Command;
         if self.common.wine {
             cmd = Command::new("wine");
+            if let Some(prefix) = &self.common.wineprefix {
+                cmd.env("WINEPREFIX", prefix);
+            }
             cmd.arg(
                 self.common
                     .terminal_exe
@@ -64,7 +132,7 @@ impl BacktestRunner {
         debug!("running terminal: {:?}", cmd);
 
         let mut child = cmd.spawn().context("Command spawning failed")?;
-        let ret = child.wait().context("Waiting for Command failed");
+        let ret = self.wait_supervised(&mut child, cancel, on_log);
         if self.common.wine {
             // sleep a little for wine to properly terminate
             thread::sleep(time::Duration::from_millis(5000));
@@ -72,14 +140,115 @@ impl BacktestRunner {
         ret
     }
 
+    // wait for the terminal to finish while following the tester log. Kill it if it exceeds the
+    // configured timeouts or the run is cancelled
+    fn wait_supervised(
+        &self,
+        child: &mut Child,
+        cancel: &CancelToken,
+        on_log: &dyn Fn(&str),
+    ) -> Result<ExitStatus> {
+        let timeouts = &self.common.timeouts;
+        let log_path = self.get_original_log_path();
+        let mut tail = LogTail::default();
+        let started = time::Instant::now();
+        let mut last_activity = started;
+        loop {
+            let status = child.try_wait().context("Waiting for Command failed")?;
+
+            // the tester log grows with every pass. If it doesn't, the terminal hangs
+            let lines = tail.read_lines(&log_path).unwrap_or_else(|e| {
+                warn!("following the tester log failed: {:#}", e);
+                Vec::new()
+            });
+            if !lines.is_empty() {
+                last_activity = time::Instant::now();
+            }
+            for line in &lines {
+                on_log(line);
+            }
+            if let Some(status) = status {
+                return Ok(status);
+            }
+            if cancel.is_cancelled() {
+                warn!("run {} cancelled. killing the terminal", self.run.name);
+                self.kill_terminal(child);
+                return Err(Cancelled.into());
+            }
+
+            let timeout = match (timeouts.run, timeouts.inactivity) {
+                (Some(t), _) if started.elapsed().as_secs() >= t => Some((TimeoutKind::Run, t)),
+                (_, Some(t)) if last_activity.elapsed().as_secs() >= t => {
+                    Some((TimeoutKind::Inactivity, t))
+                }
+                _ => None,
+            };
+            if let Some((kind, secs)) = timeout {
+                error!("terminal hit the {} timeout of {}s. killing it", kind, secs);
+                self.kill_terminal(child);
+                return Err(TerminalTimeout {
+                    kind,
+                    secs,
+                    log: self.read_terminal_log().unwrap_or_default(),
+                }
+                .into());
+            }
+
+            thread::sleep(SUPERVISE_INTERVAL);
+        }
+    }
+
+    // kill the terminal including all its child processes
+    fn kill_terminal(&self, child: &mut Child) {
+        if cfg!(windows) {
+            let _ = Command::new("taskkill")
+                .args(["/F", "/T", "/PID", &child.id().to_string()])
+                .status();
+        }
+        if let Err(e) = child.kill() {
+            warn!("killing terminal failed: {}", e);
+        }
+        let _ = child.wait();
+        if self.common.wine {
+            // terminal64.exe is not a child of the wine loader but of the wineserver.
+            // kill all processes of the wine prefix, which only runs this terminal
+            let mut wineserver = Command::new("wineserver");
+            if let Some(prefix) = &self.common.wineprefix {
+                wineserver.env("WINEPREFIX", prefix);
+            }
+            match wineserver.arg("-k").status() {
+                Ok(status) => debug!("wineserver -k: {}", status),
+                Err(e) => warn!("killing wineserver failed: {}", e),
+            }
+        }
+    }
+
+    fn read_terminal_log(&self) -> Result<String> {
+        read_tester_log(&self.get_original_log_path())
+    }
+
     pub fn prepare_files(&self) -> Result<()> {
         self.write_indi_params()?;
         fs::create_dir_all(get_reports_dir(&self.common)?)?;
         self.write_terminal_config()?;
+        self.copy_run_files()?;
         let _ = self.delete_terminal_log();
         Ok(())
     }
 
+    // keep the terminal.ini and .set file of the run next to its report
+    fn copy_run_files(&self) -> Result<()> {
+        let report = get_reports_full_path(&self.common, &self.run)?;
+        for (file, ext) in &[
+            (self.common.workdir.join("terminal.ini"), "ini"),
+            (self.common.params_path(), "set"),
+        ] {
+            fs::copy(file, report.with_extension(ext))
+                .context(format!("copying {:?} to the reports failed", file))?;
+        }
+        Ok(())
+    }
+
     fn delete_terminal_log(&self) -> Result<()> {
         let log_path = self.get_original_log_path();
         if let Err(e) = fs::remove_file(&log_path) {
@@ -91,8 +260,8 @@ impl BacktestRunner {
     fn save_terminal_log(&self) -> Result<PathBuf> {
         let run_log = get_reports_full_path(&self.common, &self.run)?.with_extension("log");
         if fs::rename(self.get_original_log_path(), &run_log).is_ok() {
-            match fs::read(&run_log) {
-                Ok(s) => debug!("Tester output:\n{}", String::from_utf8_lossy(&s)),
+            match read_tester_log(&run_log) {
+                Ok(s) => debug!("Tester output:\n{}", s),
                 Err(e) => error!("reading {:?} failed: {:?}", &run_log, e),
             };
         } else {
@@ -106,7 +275,7 @@ impl BacktestRunner {
         self.common
             .workdir
             .join("Tester/logs")
-            .join(Local::today().format("%Y%m%d").to_string())
+            .join(Local::now().format("%Y%m%d").to_string())
             .with_extension("log")
     }
 
@@ -125,16 +294,32 @@ impl BacktestRunner {
         Ok(results)
     }
 
+    // save the tester log next to the report and parse it
+    pub fn diagnose(&self) -> RunDiagnostics {
+        let log = self
+            .save_terminal_log()
+            .and_then(|run_log| read_tester_log(&run_log));
+        match log {
+            Ok(log) => parse_tester_log(&log),
+            Err(e) => {
+                warn!("no tester log to diagnose: {}", e);
+                RunDiagnostics::default()
+            }
+        }
+    }
+
     pub fn convert_results_to_csv(&self) -> Result<i32> {
-        let _ = self.save_terminal_log();
         let reports_path = get_reports_full_path(&self.common, &self.run)?;
-        let ret = read_results_xml_to_csv(&reports_path, &reports_path.with_extension("csv"));
-        ret
+        read_results_xml_to_csv(&reports_path, &reports_path.with_extension("csv"))
     }
 
+    // removes the report, the .set file and the tester log of the run. Tries all of them even if
+    // one fails
     pub fn cleanup(&self) -> Result<()> {
-        self.delete_xml_report()?;
-        self.delete_terminal_log()
+        let report = self.delete_xml_report();
+        let params = self.delete_indi_params();
+        self.delete_terminal_log()?;
+        report.and(params)
     }
 
     pub fn _remove_sqlite_file(&self) -> std::result::Result<(), std::io::Error> {
@@ -148,70 +333,225 @@ impl BacktestRunner {
     }
 }
 
-pub fn execute_run_queue(config: &CommonParams, runs: &Vec<RunParams>) -> Result<()> {
-    for r in runs {
-        debug!(
-            "Run: {:?}\nInputs: {}",
-            r,
-            r.indi_set.count_inputs_crossed()
-        );
-        let runner = BacktestRunner::new(r.clone(), &config);
-        // if let Err(err) = runner.remove_sqlite_db() { // TODO this should be done from within the Expert
-        //     warn!("delete sqlite failed {:?}", err);
-        // };
-        runner.prepare_files().context("prepare failed")?;
-        runner.run().context("run failed")?;
-        runner
-            .convert_results_to_csv()
-            .context("convert to csv failed")?;
-        runner.cleanup().context("cleanup failed")?;
-    }
-    Ok(())
+// stage of a single run in the queue reported to the caller of execute_run_queue_with
+#[derive(Debug, PartialEq, Clone)]
+pub enum RunStage {
+    Running,
+    Converting,
+    // a line of the tester log written while the terminal is running
+    Log(String),
+    Done(RunOutput),
+    Failed(String),
+    // the run was stopped or never started because the queue was cancelled
+    Cancelled,
+}
+
+// the results of a single run of the queue
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct RunOutput {
+    pub csv: PathBuf,
+    pub diagnostics: RunDiagnostics,
+}
+
+pub fn execute_run_queue(config: &CommonParams, runs: &[RunParams]) -> Result<Vec<RunOutput>> {
+    execute_run_queue_with(config, runs, &CancelToken::default(), |_, _| ())
 }
 
-pub fn get_csv_filenames_from_queue(
+// Executes the queue on all terminal instances of the config in parallel. Every instance takes
+// the next run from the queue as soon as it is idle. After the first failure no further runs are
+// started. After cancel the running runs are stopped and all runs that didn't finish are reported
+// as Cancelled. Returns the results in the order of the queue.
+pub fn execute_run_queue_with<F>(
     config: &CommonParams,
-    runs: &Vec<RunParams>,
-) -> Vec<PathBuf> {
-    runs.iter()
-        .map(|r| {
-            config
-                .reports
-                .join(r.get_reports_filename())
-                .with_extension("csv")
+    runs: &[RunParams],
+    cancel: &CancelToken,
+    on_stage: F,
+) -> Result<Vec<RunOutput>>
+where
+    F: Fn(usize, RunStage) + Send + Sync + 'static,
+{
+    config.check_wineprefixes()?;
+    let queue = Arc::new(Mutex::new(
+        runs.iter().cloned().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let outputs = Arc::new(Mutex::new(vec![None; runs.len()]));
+    let failed = Arc::new(AtomicBool::new(false));
+    let on_stage = Arc::new(on_stage);
+
+    let workers = config
+        .instances()
+        .into_iter()
+        .map(|instance| {
+            let queue = queue.clone();
+            let outputs = outputs.clone();
+            let failed = failed.clone();
+            let cancel = cancel.clone();
+            let on_stage = on_stage.clone();
+            thread::spawn(move || -> Result<()> {
+                while !failed.load(Ordering::SeqCst) && !cancel.is_cancelled() {
+                    let next = queue.lock().expect("run queue lock poisoned").pop_front();
+                    let (i, run) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    debug!("terminal {:?} takes run {}", instance.workdir, i);
+                    match execute_run(&instance, &run, &cancel, |stage| (*on_stage)(i, stage)) {
+                        Ok(output) => {
+                            (*on_stage)(i, RunStage::Done(output.clone()));
+                            outputs.lock().expect("outputs lock poisoned")[i] = Some(output)
+                        }
+                        Err(e) if is_cancelled(&e) => {
+                            (*on_stage)(i, RunStage::Cancelled);
+                            return Err(e);
+                        }
+                        Err(e) => {
+                            (*on_stage)(i, RunStage::Failed(format!("{:#}", e)));
+                            failed.store(true, Ordering::SeqCst);
+                            return Err(e.context(format!(
+                                "run {} on terminal {:?} failed",
+                                i, instance.workdir
+                            )));
+                        }
+                    }
+                }
+                Ok(())
+            })
         })
-        .collect()
-}
+        .collect::<Vec<_>>();
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    let mut first_err = None;
+    for worker in workers {
+        let ret = worker
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("terminal worker panicked")));
+        if let Err(e) = ret {
+            if !is_cancelled(&e) {
+                error!("{:#}", e);
+            }
+            first_err.get_or_insert(e);
+        }
+    }
+    if cancel.is_cancelled() {
+        let skipped = queue.lock().expect("run queue lock poisoned").split_off(0);
+        for (i, _) in skipped {
+            (*on_stage)(i, RunStage::Cancelled);
+        }
+        info!("queue cancelled");
+        return Err(Cancelled.into());
+    }
+    if let Some(e) = first_err {
+        return Err(e);
+    }
 
-    #[test]
-    fn test_get_csv_filenames_from_queue() {
-        let common = CommonParams::_new_test();
-        assert_eq!(
-            get_csv_filenames_from_queue(&common, &Vec::new()),
-            Vec::<PathBuf>::new()
-        );
+    let outputs = outputs.lock().expect("outputs lock poisoned");
+    outputs
+        .iter()
+        .cloned()
+        .collect::<Option<Vec<RunOutput>>>()
+        .context("not all runs of the queue were executed")
+}
 
-        let runs = vec![RunParams::_new_test(1)];
+pub fn execute_run<F>(
+    config: &CommonParams,
+    run: &RunParams,
+    cancel: &CancelToken,
+    on_stage: F,
+) -> Result<RunOutput>
+where
+    F: Fn(RunStage),
+{
+    debug!(
+        "Run: {:?}\nInputs: {}",
+        run,
+        run.indi_set.count_inputs_crossed().unwrap_or_default()
+    );
+    let runner = BacktestRunner::new(run.clone(), config);
+    // if let Err(err) = runner.remove_sqlite_db() { // TODO this should be done from within the Expert
+    //     warn!("delete sqlite failed {:?}", err);
+    // };
+    let mut attempt = 0;
+    loop {
+        runner.prepare_files().context("prepare failed")?;
+        on_stage(RunStage::Running);
+        match runner.run(cancel, &|line| on_stage(RunStage::Log(line.to_string()))) {
+            Ok(status) => {
+                debug!("terminal exited with {}", status);
+                break;
+            }
+            Err(e)
+                if e.downcast_ref::<TerminalTimeout>().is_some()
+                    && attempt < config.timeouts.retries =>
+            {
+                attempt += 1;
+                warn!(
+                    "{}\nretrying run {} ({}/{})",
+                    e, run.name, attempt, config.timeouts.retries
+                );
+            }
+            Err(e) if is_cancelled(&e) => {
+                if let Err(err) = runner.cleanup() {
+                    debug!("cleanup of the cancelled run {}: {:#}", run.name, err);
+                }
+                return Err(e);
+            }
+            Err(e) => return Err(e.context("run failed")),
+        }
+    }
+    on_stage(RunStage::Converting);
+    let diagnostics = runner.diagnose();
+    if diagnostics.is_fatal() {
+        let _ = runner.cleanup();
+        return Err(TesterFailed { diagnostics }.into());
+    }
+    runner
+        .convert_results_to_csv()
+        .context("convert to csv failed")?;
+    runner.cleanup().context("cleanup failed")?;
+    Ok(RunOutput {
+        csv: get_reports_full_path(config, run)?.with_extension("csv"),
+        diagnostics,
+    })
+}
 
-        assert_eq!(
-            get_csv_filenames_from_queue(&common, &runs),
-            [PathBuf::from(r"reports/test_USDCHF.csv")]
-        );
+// merge the csv files of a queue created by split_run_into_queue into <reports>/<name>.csv
+pub fn merge_queue_results(
+    config: &CommonParams,
+    name: &str,
+    runs: &[RunParams],
+    outputs: &[RunOutput],
+) -> Result<PathBuf> {
+    let reports_dir = get_reports_dir(config)?;
+    fs::create_dir_all(&reports_dir)?;
+    let merged = reports_dir.join(format!("{}.csv", name));
+    let parts = runs
+        .iter()
+        .cloned()
+        .zip(outputs.iter().map(|o| o.csv.clone()))
+        .collect::<Vec<_>>();
+    let rows = merge_csv_results(&parts, &merged).context("merging results failed")?;
+    info!(
+        "merged {} rows of {} runs into {:?}",
+        rows,
+        runs.len(),
+        merged
+    );
+    Ok(merged)
+}
 
-        let mut runs = vec![RunParams::_new_test(1), RunParams::_new_test(1)];
-        runs[1].symbols = vec!["NZDAUD".into()];
+// the files of a run that are kept in the reports dir of the terminal it ran on
+const RUN_ARTEFACTS: &[&str] = &["csv", "log", "ini", "set"];
 
-        assert_eq!(
-            get_csv_filenames_from_queue(&common, &runs),
-            [
-                PathBuf::from(r"reports/test_USDCHF.csv"),
-                PathBuf::from(r"reports/test_NZDAUD.csv")
-            ]
+// the artefacts of the run that exist on any of the terminals
+pub fn get_run_artefacts(config: &CommonParams, run: &RunParams) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for instance in config.instances() {
+        let report = get_reports_full_path(&instance, run)?;
+        files.extend(
+            RUN_ARTEFACTS
+                .iter()
+                .map(|ext| report.with_extension(ext))
+                .filter(|f| f.exists()),
         );
-        // FIXME this is not working.. never fails!!!
     }
+    Ok(files)
 }
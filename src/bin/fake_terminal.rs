@@ -0,0 +1,243 @@
+// A stand-in for terminal64.exe to test backtestd without MetaTrader.
+//
+// Usage: fake_terminal /config:terminal.ini
+//
+// Reads the terminal.ini and the .set file written by backtestd from the current directory and
+// writes a synthetic optimization report and tester log to the paths MT5 would use.
+// FAKE_TERMINAL_MODE selects a failure mode:
+//   ok                 (default) report and log of a successful optimization
+//   crash              exit with code 1 without writing anything
+//   hang               never terminate
+//   no_report          write the log but no report
+//   missing_indicator  write a log with a missing indicator and an empty report
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::{thread, time};
+
+// limit the size of the generated report
+const MAX_PASSES: usize = 10_000;
+
+fn main() -> Result<()> {
+    let config = env::args()
+        .skip(1)
+        .find_map(|a| a.strip_prefix("/config:").map(PathBuf::from))
+        .context("missing /config:<terminal.ini>")?;
+    let mode = env::var("FAKE_TERMINAL_MODE").unwrap_or_else(|_| "ok".to_string());
+
+    match mode.as_str() {
+        "crash" => std::process::exit(1),
+        "hang" => loop {
+            thread::sleep(time::Duration::from_secs(60));
+        },
+        _ => (),
+    }
+
+    let ini = parse_ini(&fs::read_to_string(&config).context("reading terminal.ini failed")?);
+    let tester = ini.get("Tester").context("no [Tester] section")?;
+    let params_file = Path::new("MQL5/Profiles/Tester").join(
+        tester
+            .get("ExpertParameters")
+            .context("no ExpertParameters")?,
+    );
+    let report = PathBuf::from(
+        tester
+            .get("Report")
+            .context("no Report")?
+            .replace('\\', "/"),
+    );
+
+    let inputs = parse_optimized_inputs(
+        &fs::read_to_string(&params_file).context(format!("reading {:?} failed", params_file))?,
+    );
+    let passes = cross_inputs(&inputs);
+
+    let mut log = vec![
+        "0\tTester\tfake terminal started".to_string(),
+        "0\tCore 1\tconnected".to_string(),
+    ];
+    if mode == "missing_indicator" {
+        log.push("2\tCore 1\tcannot load custom indicator 'fake' [4802]".to_string());
+        write_log(&log)?;
+        return write_report(&report, &inputs, &[]);
+    }
+
+    log.extend(
+        passes
+            .iter()
+            .enumerate()
+            .map(|(i, _)| format!("0\tCore 1\tpass {} returned result {}.00", i, i)),
+    );
+    log.push(format!(
+        "0\tTester\toptimization finished, total passes {}",
+        passes.len()
+    ));
+    write_log(&log)?;
+
+    if mode != "no_report" {
+        write_report(&report, &inputs, &passes)?;
+    }
+    Ok(())
+}
+
+fn parse_ini(ini: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections = HashMap::<String, HashMap<String, String>>::new();
+    let mut section = String::new();
+    for line in ini.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].to_string();
+        } else if let Some(idx) = line.find('=') {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(line[..idx].to_string(), line[idx + 1..].to_string());
+        }
+    }
+    sections
+}
+
+// name and values of every input that is flagged for optimization
+// <key>=<value>||<start>||<step>||<stop>||Y
+fn parse_optimized_inputs(set: &str) -> Vec<(String, Vec<f64>)> {
+    set.lines()
+        .filter_map(|line| {
+            let idx = line.find('=')?;
+            let fields = line[idx + 1..].split("||").collect::<Vec<_>>();
+            if fields.len() != 5 || fields[4] != "Y" {
+                return None;
+            }
+            let start: f64 = fields[1].parse().ok()?;
+            let step: f64 = fields[2].parse().ok()?;
+            let stop: f64 = fields[3].parse().ok()?;
+            if step == 0. {
+                return Some((line[..idx].to_string(), vec![start]));
+            }
+            let count = ((stop - start) / step).floor() as i64 + 1;
+            Some((
+                line[..idx].to_string(),
+                (0..count.max(1)).map(|i| start + i as f64 * step).collect(),
+            ))
+        })
+        .collect()
+}
+
+fn cross_inputs(inputs: &[(String, Vec<f64>)]) -> Vec<Vec<f64>> {
+    let mut passes = vec![Vec::new()];
+    for (_, values) in inputs {
+        passes = passes
+            .into_iter()
+            .flat_map(|p| {
+                values.iter().map(move |v| {
+                    let mut p = p.clone();
+                    p.push(*v);
+                    p
+                })
+            })
+            .take(MAX_PASSES)
+            .collect();
+    }
+    passes
+}
+
+// lines are <level>\t<source>\t<message>
+// MT5 writes the tester log as UTF-16LE with BOM
+fn write_log(lines: &[String]) -> Result<()> {
+    let log_dir = Path::new("Tester/logs");
+    fs::create_dir_all(log_dir)?;
+    let now = Local::now();
+    let text = lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| {
+            let idx = l.find('\t').unwrap_or_default();
+            format!(
+                "FK{:02}\t{}\t{}{}\r\n",
+                i % 100,
+                &l[..idx],
+                now.format("%H:%M:%S%.3f"),
+                &l[idx..]
+            )
+        })
+        .collect::<String>();
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes().to_vec()));
+    fs::write(
+        log_dir
+            .join(now.format("%Y%m%d").to_string())
+            .with_extension("log"),
+        bytes,
+    )?;
+    Ok(())
+}
+
+fn write_report(report: &Path, inputs: &[(String, Vec<f64>)], passes: &[Vec<f64>]) -> Result<()> {
+    if let Some(dir) = report.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let row = |cells: Vec<String>, kind: &str| -> String {
+        format!(
+            "<Row>\n{}</Row>\n",
+            cells
+                .iter()
+                .map(|c| format!("<Cell><Data ss:Type=\"{}\">{}</Data></Cell>\n", kind, c))
+                .collect::<String>()
+        )
+    };
+
+    let mut header = [
+        "Pass",
+        "Result",
+        "Profit",
+        "Expected Payoff",
+        "Profit Factor",
+        "Recovery Factor",
+        "Sharpe Ratio",
+        "Custom",
+        "Equity DD %",
+        "Trades",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect::<Vec<_>>();
+    // the report names the inputs Confirm_double0 while the .set file has Confirm_input0
+    header.extend(
+        inputs
+            .iter()
+            .map(|(name, _)| name.replacen("_input", "_double", 1)),
+    );
+
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet"
+xmlns:ss="urn:schemas-microsoft-com:office:spreadsheet">
+<Worksheet ss:Name="Tester Optimizator Results">
+<Table>
+"#,
+    );
+    xml.push_str(&row(header, "String"));
+    for (i, values) in passes.iter().enumerate() {
+        // deterministic but different metrics for every pass
+        let x = values.iter().sum::<f64>() + i as f64;
+        let mut cells = vec![
+            i.to_string(),
+            format!("{:.2}", x),
+            format!("{:.2}", x * 10. - 100.),
+            format!("{:.6}", x / 100.),
+            format!("{:.6}", 1. + x / 1000.),
+            format!("{:.6}", x / 50.),
+            format!("{:.6}", x / 200.),
+            format!("{}", x / 3.),
+            format!("{:.4}", 5. + (i % 10) as f64),
+            format!("{}", 50 + i % 100),
+        ];
+        cells.extend(values.iter().map(|v| v.to_string()));
+        xml.push_str(&row(cells, "Number"));
+    }
+    xml.push_str("</Table>\n</Worksheet>\n</Workbook>\n");
+
+    fs::write(report, xml).context(format!("writing {:?} failed", report))?;
+    Ok(())
+}
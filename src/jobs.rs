@@ -0,0 +1,622 @@
+use crate::backtest_runner::{self, is_cancelled, CancelToken, RunOutput, RunStage};
+use crate::journal::{self, EntryId, RunJournal};
+use crate::params::*;
+use crate::results::tester_log::LogLine;
+
+use anyhow::Result;
+use chrono::prelude::*;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+pub type JobId = u64;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Converting,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: JobId,
+    pub name: String,
+    pub state: JobState,
+    pub runs: Vec<RunParams>,
+    // ids of the runs in the journal
+    pub journal_ids: Vec<EntryId>,
+    pub current_run: Option<usize>,
+    // finished passes reported in the tester logs of the runs
+    pub passes_done: u64,
+    // the combinations of all runs
+    pub passes: u64,
+    pub results: Vec<RunOutput>,
+    pub merged: Option<PathBuf>,
+    pub error: Option<String>,
+    pub submitted: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    pub cancel: CancelToken,
+}
+
+// what the API returns for GET /jobs/{id}
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct JobStatus {
+    pub id: JobId,
+    pub name: String,
+    pub state: JobState,
+    pub current_run: Option<usize>,
+    pub runs: usize,
+    pub passes_done: u64,
+    pub passes: u64,
+    pub error: Option<String>,
+    pub submitted: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+impl From<&Job> for JobStatus {
+    fn from(job: &Job) -> Self {
+        JobStatus {
+            id: job.id,
+            name: job.name.clone(),
+            state: job.state,
+            current_run: job.current_run,
+            runs: job.runs.len(),
+            passes_done: job.passes_done,
+            passes: job.passes,
+            error: job.error.clone(),
+            submitted: job.submitted,
+            finished: job.finished,
+        }
+    }
+}
+
+// what GET /jobs/{id}/events streams
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum JobEvent {
+    // the number of jobs that run before this one
+    Queued {
+        position: usize,
+    },
+    State {
+        state: JobState,
+    },
+    // a run of the queue changed its state on a terminal
+    Run {
+        run: usize,
+        name: String,
+        state: JobState,
+    },
+    Passes {
+        done: u64,
+        total: u64,
+    },
+    // a line of the tester log of a run
+    Log {
+        run: usize,
+        line: String,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Jobs {
+    next_id: JobId,
+    jobs: HashMap<JobId, Job>,
+    queue: VecDeque<JobId>,
+    // the event streams of the jobs. They are closed when the job is finished
+    listeners: HashMap<JobId, Vec<UnboundedSender<JobEvent>>>,
+}
+
+impl Jobs {
+    fn emit(&mut self, id: JobId, event: JobEvent) {
+        if let Some(listeners) = self.listeners.get_mut(&id) {
+            listeners.retain(|l| l.unbounded_send(event.clone()).is_ok());
+        }
+    }
+
+    // tell the queued jobs that they moved up
+    fn emit_positions(&mut self) {
+        let queue = self.queue.iter().cloned().enumerate().collect::<Vec<_>>();
+        for (position, id) in queue {
+            self.emit(id, JobEvent::Queued { position });
+        }
+    }
+}
+
+// Jobs submitted via the API are queued here and drained by a single background worker.
+// The handle is cheap to clone and shared between all actix workers.
+#[derive(Debug, Clone, Default)]
+pub struct JobQueue {
+    inner: Arc<(Mutex<Jobs>, Condvar)>,
+    journal: Option<Arc<RunJournal>>,
+}
+
+impl JobQueue {
+    // persist all runs in the journal
+    pub fn with_journal(journal: Arc<RunJournal>) -> Self {
+        JobQueue {
+            journal: Some(journal),
+            ..Default::default()
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Jobs> {
+        self.inner.0.lock().expect("job queue lock poisoned")
+    }
+
+    // runs is the queue of a single logical run called name as created by split_run_into_queue
+    pub fn submit(&self, name: String, runs: Vec<RunParams>) -> Result<JobId> {
+        let journal_ids = match &self.journal {
+            Some(journal) => journal.enqueue(&name, &runs)?,
+            None => Vec::new(),
+        };
+        Ok(self.push(name, runs, journal_ids))
+    }
+
+    // submit all runs of the journal that were queued or interrupted
+    pub fn resume_pending(&self) -> Vec<JobId> {
+        let pending = match &self.journal {
+            Some(journal) => journal.pending(),
+            None => return Vec::new(),
+        };
+        pending
+            .into_iter()
+            .map(|(name, entries)| {
+                info!("resuming {} runs of {}", entries.len(), name);
+                let ids = entries.iter().map(|e| e.id).collect();
+                let runs = entries.into_iter().map(|e| e.run).collect();
+                self.push(name, runs, ids)
+            })
+            .collect()
+    }
+
+    fn push(&self, name: String, runs: Vec<RunParams>, journal_ids: Vec<EntryId>) -> JobId {
+        let mut jobs = self.lock();
+        jobs.next_id += 1;
+        let id = jobs.next_id;
+        let passes = runs.iter().fold(0u64, |sum, r| {
+            sum.saturating_add(r.indi_set.count_inputs_crossed().unwrap_or_default())
+        });
+        jobs.jobs.insert(
+            id,
+            Job {
+                id,
+                name,
+                state: JobState::Queued,
+                runs,
+                journal_ids,
+                current_run: None,
+                passes_done: 0,
+                passes,
+                results: Vec::new(),
+                merged: None,
+                error: None,
+                submitted: Utc::now(),
+                finished: None,
+                cancel: CancelToken::default(),
+            },
+        );
+        jobs.queue.push_back(id);
+        self.inner.1.notify_one();
+        info!("queued job {}", id);
+        id
+    }
+
+    pub fn get(&self, id: JobId) -> Option<Job> {
+        self.lock().jobs.get(&id).cloned()
+    }
+
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.lock().jobs.get(&id).map(JobStatus::from)
+    }
+
+    // changes the job with f and sends the events returned by f to the listeners of the job
+    fn update_and_emit<F: FnOnce(&mut Job) -> Vec<JobEvent>>(&self, id: JobId, f: F) {
+        let mut jobs = self.lock();
+        let events = match jobs.jobs.get_mut(&id) {
+            Some(job) => f(job),
+            None => return,
+        };
+        for event in events {
+            jobs.emit(id, event);
+        }
+    }
+
+    // The events of the job starting with its current state. The stream ends when the job is
+    // finished.
+    pub fn subscribe(&self, id: JobId) -> Option<UnboundedReceiver<JobEvent>> {
+        let mut jobs = self.lock();
+        let job = jobs.jobs.get(&id)?;
+        let (tx, rx) = unbounded();
+        let mut snapshot = vec![JobEvent::State { state: job.state }];
+        if let Some(position) = jobs.queue.iter().position(|q| *q == id) {
+            snapshot.push(JobEvent::Queued { position });
+        }
+        if let Some(run) = job.current_run {
+            snapshot.push(JobEvent::Run {
+                run,
+                name: job.runs[run].name.clone(),
+                state: job.state,
+            });
+        }
+        snapshot.push(JobEvent::Passes {
+            done: job.passes_done,
+            total: job.passes,
+        });
+        let finished = job.finished.is_some();
+        for event in snapshot {
+            let _ = tx.unbounded_send(event);
+        }
+        if !finished {
+            jobs.listeners.entry(id).or_default().push(tx);
+        }
+        Some(rx)
+    }
+
+    // A queued job is cancelled right away, a running job is stopped by the worker. None if the
+    // job doesn't exist, false if it's already finished.
+    pub fn cancel(&self, id: JobId) -> Option<bool> {
+        let mut jobs = self.lock();
+        let job = jobs.jobs.get_mut(&id)?;
+        if job.finished.is_some() {
+            return Some(false);
+        }
+        info!("cancelling job {}", id);
+        job.cancel.cancel();
+
+        let position = match jobs.queue.iter().position(|q| *q == id) {
+            Some(position) => position,
+            None => return Some(true),
+        };
+        jobs.queue.remove(position);
+        let job = jobs.jobs.get_mut(&id).unwrap();
+        job.state = JobState::Cancelled;
+        job.finished = Some(Utc::now());
+        if let Some(journal) = &self.journal {
+            for entry in &job.journal_ids {
+                if let Err(e) = journal.set_state(*entry, journal::EntryState::Cancelled) {
+                    error!("updating journal entry {} failed: {:#}", entry, e);
+                }
+            }
+        }
+        jobs.emit(
+            id,
+            JobEvent::State {
+                state: JobState::Cancelled,
+            },
+        );
+        jobs.listeners.remove(&id);
+        jobs.emit_positions();
+        Some(true)
+    }
+
+    // blocks until a job is queued
+    fn next(&self) -> Job {
+        let mut jobs = self.lock();
+        loop {
+            if let Some(id) = jobs.queue.pop_front() {
+                jobs.emit_positions();
+                return jobs.jobs[&id].clone();
+            }
+            jobs = self.inner.1.wait(jobs).expect("job queue lock poisoned");
+        }
+    }
+
+    // update the job with the stage of one of its runs
+    fn record_stage(&self, id: JobId, run: usize, stage: RunStage) {
+        self.update_and_emit(id, |j| {
+            let run_state = match stage {
+                RunStage::Running => JobState::Running,
+                RunStage::Converting => JobState::Converting,
+                RunStage::Done(_) => JobState::Done,
+                RunStage::Failed(_) => JobState::Failed,
+                RunStage::Cancelled => JobState::Cancelled,
+                RunStage::Log(line) => {
+                    let pass = LogLine::parse(&line).is_some_and(|l| l.is_finished_pass());
+                    let mut events = vec![JobEvent::Log { run, line }];
+                    if pass {
+                        j.passes_done += 1;
+                        events.push(JobEvent::Passes {
+                            done: j.passes_done,
+                            total: j.passes,
+                        });
+                    }
+                    return events;
+                }
+            };
+            j.current_run = Some(run);
+            let mut events = vec![JobEvent::Run {
+                run,
+                name: j.runs[run].name.clone(),
+                state: run_state,
+            }];
+            match run_state {
+                JobState::Running | JobState::Converting if j.state != run_state => {
+                    j.state = run_state;
+                    events.push(JobEvent::State { state: j.state });
+                }
+                _ => (),
+            };
+            events
+        })
+    }
+
+    pub fn start_worker(&self, config: CommonParams) -> thread::JoinHandle<()> {
+        let queue = self.clone();
+        thread::spawn(move || loop {
+            let job = queue.next();
+            let q = queue.clone();
+            queue.run_job(job, |job| {
+                let id = job.id;
+                let on_stage = move |i: usize, stage: RunStage| q.record_stage(id, i, stage);
+                let outputs = match &queue.journal {
+                    Some(journal) => journal::execute_journaled(
+                        &config,
+                        journal,
+                        &job.journal_ids,
+                        &job.runs,
+                        &job.cancel,
+                        on_stage,
+                    ),
+                    None => backtest_runner::execute_run_queue_with(
+                        &config,
+                        &job.runs,
+                        &job.cancel,
+                        on_stage,
+                    ),
+                }?;
+                let merged =
+                    backtest_runner::merge_queue_results(&config, &job.name, &job.runs, &outputs)?;
+                Ok((outputs, merged))
+            });
+        })
+    }
+
+    // Executes the job and records its result. A panic only fails the job, so the worker goes on
+    // with the next one.
+    fn run_job<F>(&self, job: Job, execute: F)
+    where
+        F: FnOnce(&Job) -> Result<(Vec<RunOutput>, PathBuf)>,
+    {
+        let id = job.id;
+        info!("starting job {} with {} runs", id, job.runs.len());
+        self.update_and_emit(id, |j| {
+            j.state = JobState::Running;
+            vec![JobEvent::State { state: j.state }]
+        });
+
+        let ret = panic::catch_unwind(AssertUnwindSafe(|| execute(&job))).unwrap_or_else(|p| {
+            let msg = p
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| p.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(anyhow!("job panicked: {}", msg))
+        });
+
+        self.update_and_emit(id, |j| {
+            j.finished = Some(Utc::now());
+            match ret {
+                Ok((outputs, merged)) => {
+                    info!("job {} done", id);
+                    j.state = JobState::Done;
+                    j.results = outputs;
+                    j.merged = Some(merged);
+                }
+                Err(e) if is_cancelled(&e) => {
+                    info!("job {} cancelled", id);
+                    j.state = JobState::Cancelled;
+                }
+                Err(e) => {
+                    error!("job {} failed: {:?}", id, e);
+                    j.state = JobState::Failed;
+                    j.error = Some(format!("{:#}", e));
+                }
+            }
+            vec![JobEvent::State { state: j.state }]
+        });
+        self.lock().listeners.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn job_queue_test() {
+        let queue = JobQueue::default();
+        assert!(queue.status(1).is_none());
+
+        let id = queue
+            .submit("test".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        let id2 = queue
+            .submit(
+                "test2".into(),
+                vec![RunParams::_new_test(1), RunParams::_new_test(2)],
+            )
+            .unwrap();
+        assert_ne!(id, id2);
+
+        let status = queue.status(id).unwrap();
+        assert_eq!(status.state, JobState::Queued);
+        assert_eq!(status.name, "test");
+        assert_eq!(status.runs, 1);
+        assert_eq!(queue.status(id2).unwrap().runs, 2);
+        assert_eq!(queue.status(id2).unwrap().name, "test2");
+
+        // jobs are handed out in submission order
+        assert_eq!(queue.next().id, id);
+        assert_eq!(queue.next().id, id2);
+
+        queue.update_and_emit(id, |j| {
+            j.state = JobState::Done;
+            Vec::new()
+        });
+        assert_eq!(queue.get(id).unwrap().state, JobState::Done);
+    }
+
+    fn next_event(events: &mut UnboundedReceiver<JobEvent>) -> Option<JobEvent> {
+        events.try_next().expect("no event sent")
+    }
+
+    #[test]
+    fn job_events_test() {
+        let queue = JobQueue::default();
+        assert!(queue.subscribe(1).is_none());
+
+        let id = queue
+            .submit("test".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        let id2 = queue
+            .submit("test2".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        let total = queue.get(id2).unwrap().passes;
+        assert!(total > 0);
+
+        let mut events = queue.subscribe(id2).unwrap();
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::State {
+                state: JobState::Queued
+            })
+        );
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Queued { position: 1 })
+        );
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Passes { done: 0, total })
+        );
+
+        assert_eq!(queue.next().id, id);
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Queued { position: 0 })
+        );
+        assert_eq!(queue.next().id, id2);
+
+        queue.record_stage(id2, 0, RunStage::Running);
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Run {
+                run: 0,
+                name: "test".into(),
+                state: JobState::Running
+            })
+        );
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::State {
+                state: JobState::Running
+            })
+        );
+        let line = "GL\t0\t09:51:09.123\tCore 1\tpass 0 returned result 1234.00 in 0:00:01.234";
+        queue.record_stage(id2, 0, RunStage::Log(line.into()));
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Log {
+                run: 0,
+                line: line.into()
+            })
+        );
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Passes { done: 1, total })
+        );
+        assert_eq!(queue.status(id2).unwrap().passes_done, 1);
+
+        // the stream ends with the job
+        queue.lock().listeners.remove(&id2);
+        assert_eq!(next_event(&mut events), None);
+    }
+
+    #[test]
+    fn job_cancel_test() {
+        let queue = JobQueue::default();
+        assert_eq!(queue.cancel(1), None);
+
+        let id = queue
+            .submit("test".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        let id2 = queue
+            .submit("test2".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        let mut events = queue.subscribe(id2).unwrap();
+        for _ in 0..3 {
+            next_event(&mut events);
+        }
+
+        // a queued job is cancelled right away
+        assert_eq!(queue.cancel(id), Some(true));
+        let status = queue.status(id).unwrap();
+        assert_eq!(status.state, JobState::Cancelled);
+        assert!(status.finished.is_some());
+        assert_eq!(
+            next_event(&mut events),
+            Some(JobEvent::Queued { position: 0 })
+        );
+        assert_eq!(queue.cancel(id), Some(false));
+
+        // a running job is stopped by the worker
+        assert_eq!(queue.next().id, id2);
+        assert_eq!(queue.cancel(id2), Some(true));
+        assert!(queue.get(id2).unwrap().cancel.is_cancelled());
+        assert!(queue.status(id2).unwrap().finished.is_none());
+    }
+
+    #[test]
+    fn job_panic_test() {
+        let queue = JobQueue::default();
+        let id = queue
+            .submit("test".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        let id2 = queue
+            .submit("test2".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+
+        // a panic fails the job instead of ending the worker
+        queue.run_job(queue.next(), |_| panic!("boom"));
+        let status = queue.status(id).unwrap();
+        assert_eq!(status.state, JobState::Failed);
+        assert_eq!(status.error.unwrap(), "job panicked: boom");
+        assert!(status.finished.is_some());
+
+        queue.run_job(queue.next(), |_| Err(anyhow!("broken")));
+        let status = queue.status(id2).unwrap();
+        assert_eq!(status.state, JobState::Failed);
+        assert_eq!(status.error.unwrap(), "broken");
+    }
+
+    #[test]
+    fn job_queue_journal_test() {
+        let path = std::path::Path::new("/tmp/backtestd_job_queue_test.jsonl");
+        let _ = std::fs::remove_file(path);
+
+        let queue = JobQueue::with_journal(Arc::new(RunJournal::open(path).unwrap()));
+        let id = queue
+            .submit("test".into(), vec![RunParams::_new_test(1)])
+            .unwrap();
+        assert_eq!(queue.get(id).unwrap().journal_ids, vec![1]);
+
+        // after a restart the queued run is resumed
+        drop(queue);
+        let queue = JobQueue::with_journal(Arc::new(RunJournal::open(path).unwrap()));
+        let resumed = queue.resume_pending();
+        assert_eq!(resumed.len(), 1);
+        let job = queue.get(resumed[0]).unwrap();
+        assert_eq!(job.name, "test");
+        assert_eq!(job.runs, vec![RunParams::_new_test(1)]);
+        assert_eq!(job.journal_ids, vec![1]);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::backtest_runner::{self, CancelToken, RunOutput, RunStage};
+use crate::params::*;
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{prelude::*, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub type EntryId = u64;
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Dropped,
+    // stopped by Ctrl-C or DELETE /jobs/{id}. Continued when the run is started again
+    Cancelled,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    pub id: EntryId,
+    // name of the logical run the entry was split from
+    pub job: String,
+    pub run: RunParams,
+    pub state: EntryState,
+    pub output: Option<RunOutput>,
+    pub error: Option<String>,
+    pub updated: DateTime<Utc>,
+}
+
+impl JournalEntry {
+    // done and the results are still there
+    pub fn is_complete(&self) -> bool {
+        self.state == EntryState::Done && self.output.as_ref().is_some_and(|o| o.csv.exists())
+    }
+}
+
+// The queue of runs is persisted as a json-lines journal. Every change of an entry appends the
+// whole entry, the last line of an id wins. The journal is compacted when it's opened.
+#[derive(Debug)]
+pub struct RunJournal {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<EntryId, JournalEntry>>,
+    // Locked as long as the journal is open. The ids are counted in memory, so two processes
+    // writing the same journal would give out the same ids.
+    _lock: File,
+}
+
+impl RunJournal {
+    pub fn open(path: &Path) -> Result<Self> {
+        let lock_path = path.with_extension("lock");
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .context(format!("opening {:?} failed", lock_path))?;
+        lock.try_lock().map_err(|_| {
+            anyhow!(
+                "journal {:?} is used by another backtestd, set another journal in the config",
+                path
+            )
+        })?;
+
+        let mut entries = BTreeMap::new();
+        if path.exists() {
+            let file = File::open(path).context(format!("opening journal {:?} failed", path))?;
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                match serde_json::from_str::<JournalEntry>(&line?) {
+                    Ok(entry) => {
+                        entries.insert(entry.id, entry);
+                    }
+                    // the last line may be incomplete after a crash
+                    Err(e) => warn!("skipping line {} of journal {:?}: {}", i + 1, path, e),
+                }
+            }
+        }
+        let journal = RunJournal {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            _lock: lock,
+        };
+        journal.compact()?;
+        Ok(journal)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<EntryId, JournalEntry>> {
+        self.entries.lock().expect("journal lock poisoned")
+    }
+
+    fn compact(&self) -> Result<()> {
+        let entries = self.lock();
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            for entry in entries.values() {
+                writeln!(file, "{}", serde_json::to_string(entry)?)?;
+            }
+        }
+        fs::rename(&tmp, &self.path).context(format!("writing journal {:?} failed", self.path))?;
+        Ok(())
+    }
+
+    fn append(&self, entry: &JournalEntry) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context(format!("opening journal {:?} failed", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    fn modify<F: FnOnce(&mut JournalEntry)>(&self, id: EntryId, f: F) -> Result<JournalEntry> {
+        let mut entries = self.lock();
+        let entry = entries
+            .get_mut(&id)
+            .context(format!("journal entry {} not found", id))?;
+        f(entry);
+        entry.updated = Utc::now();
+        self.append(entry)?;
+        Ok(entry.clone())
+    }
+
+    // Adds the queue of a logical run to the journal. Runs that are already in the journal are
+    // not added again so an interrupted queue continues where it stopped.
+    pub fn enqueue(&self, job: &str, runs: &[RunParams]) -> Result<Vec<EntryId>> {
+        let mut entries = self.lock();
+        let mut ids = Vec::with_capacity(runs.len());
+        for run in runs {
+            let existing = entries
+                .values()
+                .find(|e| e.job == job && e.run == *run && e.state != EntryState::Dropped)
+                .map(|e| e.id);
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    let id = entries.keys().next_back().map_or(1, |id| id + 1);
+                    let entry = JournalEntry {
+                        id,
+                        job: job.to_string(),
+                        run: run.clone(),
+                        state: EntryState::Queued,
+                        output: None,
+                        error: None,
+                        updated: Utc::now(),
+                    };
+                    self.append(&entry)?;
+                    entries.insert(id, entry);
+                    id
+                }
+            };
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    pub fn get(&self, id: EntryId) -> Option<JournalEntry> {
+        self.lock().get(&id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<JournalEntry> {
+        self.lock().values().cloned().collect()
+    }
+
+    pub fn set_state(&self, id: EntryId, state: EntryState) -> Result<JournalEntry> {
+        self.modify(id, |e| e.state = state)
+    }
+
+    pub fn retry(&self, id: EntryId) -> Result<JournalEntry> {
+        self.modify(id, |e| {
+            e.state = EntryState::Queued;
+            e.output = None;
+            e.error = None;
+        })
+    }
+
+    pub fn drop_entry(&self, id: EntryId) -> Result<JournalEntry> {
+        self.set_state(id, EntryState::Dropped)
+    }
+
+    fn record(&self, id: EntryId, stage: &RunStage) {
+        let ret = match stage {
+            RunStage::Running => self.set_state(id, EntryState::Running),
+            RunStage::Converting | RunStage::Log(_) => return,
+            RunStage::Done(output) => self.modify(id, |e| {
+                e.state = EntryState::Done;
+                e.output = Some(output.clone());
+                e.error = None;
+            }),
+            RunStage::Failed(error) => self.modify(id, |e| {
+                e.state = EntryState::Failed;
+                e.error = Some(error.clone());
+            }),
+            RunStage::Cancelled => self.set_state(id, EntryState::Cancelled),
+        };
+        if let Err(e) = ret {
+            error!("updating journal entry {} failed: {:#}", id, e);
+        }
+    }
+
+    // entries that were queued or interrupted while running, grouped by their job
+    pub fn pending(&self) -> BTreeMap<String, Vec<JournalEntry>> {
+        let mut pending = BTreeMap::<String, Vec<JournalEntry>>::new();
+        for e in self.lock().values() {
+            match e.state {
+                EntryState::Queued | EntryState::Running => {
+                    pending.entry(e.job.clone()).or_default().push(e.clone())
+                }
+                _ => (),
+            }
+        }
+        pending
+    }
+}
+
+// Executes the runs of the queue that are not complete yet and records the progress in the
+// journal. The outputs of complete runs are taken from the journal.
+pub fn execute_journaled<F>(
+    config: &CommonParams,
+    journal: &Arc<RunJournal>,
+    ids: &[EntryId],
+    runs: &[RunParams],
+    cancel: &CancelToken,
+    on_stage: F,
+) -> Result<Vec<RunOutput>>
+where
+    F: Fn(usize, RunStage) + Send + Sync + 'static,
+{
+    let mut outputs = vec![None; runs.len()];
+    let mut todo = Vec::new();
+    for (i, id) in ids.iter().enumerate() {
+        match journal.get(*id) {
+            Some(ref e) if e.is_complete() => {
+                info!("skipping {} which is already done", e.run.name);
+                outputs[i] = e.output.clone();
+            }
+            _ => todo.push(i),
+        }
+    }
+
+    let todo_runs = todo.iter().map(|i| runs[*i].clone()).collect::<Vec<_>>();
+    let todo_ids = todo.iter().map(|i| ids[*i]).collect::<Vec<_>>();
+    let todo_idx = todo.clone();
+    let j = journal.clone();
+    let todo_outputs =
+        backtest_runner::execute_run_queue_with(config, &todo_runs, cancel, move |i, stage| {
+            j.record(todo_ids[i], &stage);
+            on_stage(todo_idx[i], stage);
+        })?;
+
+    for (i, output) in todo.into_iter().zip(todo_outputs) {
+        outputs[i] = Some(output);
+    }
+    outputs
+        .into_iter()
+        .collect::<Option<Vec<RunOutput>>>()
+        .context("not all runs of the queue were executed")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::results::tester_log::RunDiagnostics;
+
+    #[test]
+    fn journal_test() {
+        let path = Path::new("/tmp/backtestd_journal_test.jsonl");
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file("/tmp/backtestd_journal_test.csv");
+
+        let mut run2 = RunParams::_new_test(1);
+        run2.symbols = vec!["AUDCAD".into()];
+        let runs = vec![RunParams::_new_test(1), run2];
+
+        let journal = RunJournal::open(path).unwrap();
+        let ids = journal.enqueue("test", &runs).unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        // enqueuing the same runs again doesn't create new entries
+        assert_eq!(journal.enqueue("test", &runs).unwrap(), ids);
+        assert_eq!(journal.enqueue("other", &runs[..1]).unwrap(), vec![3]);
+
+        journal.record(1, &RunStage::Running);
+        journal.record(
+            1,
+            &RunStage::Done(RunOutput {
+                csv: PathBuf::from("/tmp/backtestd_journal_test.csv"),
+                diagnostics: RunDiagnostics::default(),
+            }),
+        );
+        journal.record(2, &RunStage::Running);
+        journal.drop_entry(3).unwrap();
+
+        // the journal is used by one process at a time
+        assert!(RunJournal::open(path).is_err());
+
+        // reopen as after a crash
+        drop(journal);
+        let journal = RunJournal::open(path).unwrap();
+        assert_eq!(journal.list().len(), 3);
+        assert_eq!(journal.get(1).unwrap().state, EntryState::Done);
+        assert_eq!(journal.get(2).unwrap().state, EntryState::Running);
+        assert_eq!(journal.get(3).unwrap().state, EntryState::Dropped);
+
+        let pending = journal.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending["test"].len(), 1);
+        assert_eq!(pending["test"][0].id, 2);
+
+        // the csv doesn't exist
+        assert!(!journal.get(1).unwrap().is_complete());
+        fs::write("/tmp/backtestd_journal_test.csv", "Pass\n").unwrap();
+        assert!(journal.get(1).unwrap().is_complete());
+
+        journal.record(2, &RunStage::Failed("broken".into()));
+        assert_eq!(journal.get(2).unwrap().error, Some("broken".into()));
+        journal.retry(2).unwrap();
+        assert_eq!(journal.get(2).unwrap().state, EntryState::Queued);
+        assert_eq!(journal.get(2).unwrap().error, None);
+
+        // dropped entries are enqueued again
+        assert_eq!(journal.enqueue("other", &runs[..1]).unwrap(), vec![4]);
+
+        // cancelled runs aren't resumed but continue when the run is started again
+        journal.record(4, &RunStage::Cancelled);
+        assert_eq!(journal.get(4).unwrap().state, EntryState::Cancelled);
+        assert!(!journal.pending().contains_key("other"));
+        assert_eq!(journal.enqueue("other", &runs[..1]).unwrap(), vec![4]);
+    }
+}
@@ -2,7 +2,10 @@
 // #![allow(dead_code)]
 // #![allow(unused)]
 #![feature(test)]
-use std::path::PathBuf;
+use anyhow::Context;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 extern crate lazy_static;
 extern crate test;
@@ -19,14 +22,28 @@ extern crate actix_web;
 extern crate chrono;
 
 use actix_web::{
-    error::ErrorInternalServerError, middleware, web, App as ActixApp, Error as ActixError,
-    HttpResponse, HttpServer,
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    middleware, web, App as ActixApp, Error as ActixError, HttpResponse, HttpServer,
 };
+mod api;
 mod backtest_runner;
 use backtest_runner::*;
+mod jobs;
+use jobs::JobQueue;
+mod journal;
+use journal::RunJournal;
 mod params;
+use params::set_file::read_mt5_file;
+use params::terminal_ini::import_terminal_ini;
 use params::*;
+mod pipeline;
+mod plan;
+use plan::Plan;
 mod results;
+use results::best::*;
+use results::robustness::*;
+use results::sweep::*;
+mod walk_forward;
 
 // running the multi-currency EA is significantly slower than running on single Symbol
 // The overhead to init the backtest is also significant
@@ -50,16 +67,114 @@ async fn main() -> std::io::Result<()> {
             (about: "run a backtest")
             (@arg INPUT: +required "yaml file the specifies the run params")
             (@arg CLEANUP: -c --cleanup "cleanup files after running the backtest")
+            (@arg PLAN: --plan "print the queue with the passes of every run and exit")
+            (@arg DRY_RUN: --("dry-run")
+                "print the terminal.ini and .set file of every run of the queue and exit")
+            (@arg SCRATCH: --scratch +takes_value
+                "with --plan or --dry-run: write the files of every run into this directory")
+        )
+        (@subcommand validate =>
+            (about: "check a run config for mistakes without running it")
+            (@arg INPUT: +required "yaml file the specifies the run params")
         )
         (@subcommand daemon =>
             (about: "start a daemon with a REST API")
         )
+        (@subcommand best =>
+            (about: "write the best passes of an optimization as indicator configs")
+            (@arg REPORT: +required "report xml or result csv of the optimization")
+            (@arg RUN: +required "yaml file of the run the report results from")
+            (@arg METRIC: -m --metric +takes_value
+                "custom, profit_factor, recovery_factor, sharpe_ratio or profit (default: custom)")
+            (@arg MIN_TRADES: --("min-trades") +takes_value "ignore passes with fewer trades")
+            (@arg TOP: -n --top +takes_value "number of passes to write (default: 5)")
+            (@arg OUT: -o --out +takes_value "output directory (default: config/indicator)")
+            (@arg FORCE: -f --force "overwrite existing indicator configs")
+        )
+        (@subcommand robustness =>
+            (about: "score the passes of an optimization by their neighbours in parameter space")
+            (@arg REPORT: +required "report xml or result csv of the optimization")
+            (@arg RUN: +required "yaml file of the run the report results from")
+            (@arg METRIC: -m --metric +takes_value
+                "custom, profit_factor, recovery_factor, sharpe_ratio or profit (default: custom)")
+            (@arg MIN_TRADES: --("min-trades") +takes_value "ignore passes with fewer trades")
+            (@arg RADIUS: -r --radius +takes_value "neighbourhood in grid steps (default: 1)")
+            (@arg TOP: -n --top +takes_value "number of passes to list (default: 20)")
+        )
+        (@subcommand pipeline =>
+            (about: "test the indicators of a catalog stage by stage and combine the winners")
+            (@arg INPUT: +required "yaml file that specifies the pipeline")
+        )
+        (@subcommand config =>
+            (about: "create the config")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand import =>
+                (about: "write the config from the terminal.ini or tester.ini of a terminal")
+                (@arg INI: +required "terminal.ini or tester.ini with a [Tester] section")
+                (@arg OUT: -o --out +takes_value
+                    "config file to write (default: config/config.yaml)")
+                (@arg RUN: -r --run +takes_value
+                    "also write the run settings of the ini to this file")
+                (@arg TERMINAL: -t --terminal +takes_value
+                    "path to terminal64.exe (default: terminal64.exe in the workdir)")
+            )
+        )
+        (@subcommand queue =>
+            (about: "manage the persisted queue of runs")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand list =>
+                (about: "list all runs in the queue")
+            )
+            (@subcommand retry =>
+                (about: "queue a run again")
+                (@arg ID: +required "id of the run")
+            )
+            (@subcommand drop =>
+                (about: "drop a run from the queue")
+                (@arg ID: +required "id of the run")
+            )
+            (@subcommand resume =>
+                (about: "execute all queued and interrupted runs")
+            )
+        )
     )
     .get_matches();
 
+    // -------------
+    // Config App
+    // -------------
+    // there is no config to read yet
+    if let Some(import) = matches
+        .subcommand_matches("config")
+        .and_then(|m| m.subcommand_matches("import"))
+    {
+        let ini = Path::new(import.value_of("INI").unwrap());
+        let workdir = match matches.value_of("WORKDIR") {
+            Some(w) => PathBuf::from(w),
+            None => ini.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let terminal_exe = match import.value_of("TERMINAL") {
+            Some(t) => PathBuf::from(t),
+            None => workdir.join("terminal64.exe"),
+        };
+        let (config, settings) = exit_on_error(
+            read_mt5_file(ini)
+                .and_then(|content| import_terminal_ini(&content, terminal_exe, workdir))
+                .context(format!("importing {:?} failed", ini)),
+        );
+        let out = Path::new(import.value_of("OUT").unwrap_or("config/config.yaml"));
+        exit_on_error(write_new_yaml(out, &config));
+        println!("config written to {}", out.display());
+        if let Some(run) = import.value_of("RUN") {
+            exit_on_error(write_new_yaml(Path::new(run), &settings));
+            println!("run settings written to {}", run);
+        }
+        return Ok(());
+    }
+
     let config_file = matches.value_of("CONFIG").unwrap_or("config/config.yaml");
     let mut config: CommonParams = serde_any::from_file(config_file)
-        .expect(&format!("reading config file failed: {}", config_file));
+        .unwrap_or_else(|_| panic!("reading config file failed: {}", config_file));
 
     if let Some(w) = matches.value_of("WORKDIR") {
         config.workdir = PathBuf::from(w);
@@ -79,45 +194,482 @@ async fn main() -> std::io::Result<()> {
     if let Some(matches) = matches.subcommand_matches("run") {
         let input_file = matches.value_of("INPUT").unwrap();
         info!("Running backtest from: {}", input_file);
-        let run: RunParams = serde_any::from_file::<RunParamsFile, _>(input_file)
-            .expect("reading RunParamsFile failed")
-            .into();
+        let run_file = exit_on_error(read_run_file(Path::new(input_file)));
+        let sweep = exit_on_error(run_file.sweep_func());
+        let name = run_file.name.clone();
+        let mut runs = exit_on_error(run_file.expand());
+        for run in &runs {
+            exit_on_error(run.validate().context(format!("invalid run {}", run.name)));
+        }
+        if matches.is_present("PLAN") || matches.is_present("DRY_RUN") {
+            for run in runs {
+                let plan = exit_on_error(Plan::new(&config, run));
+                print_plan(&plan, matches.is_present("DRY_RUN"));
+                if let Some(dir) = matches.value_of("SCRATCH") {
+                    exit_on_error(plan.write(Path::new(dir), &config));
+                    info!("files of {} written to {}", plan.name, dir);
+                }
+            }
+            return Ok(());
+        }
+
+        let journal = open_journal(&config);
+        let cancel = cancel_on_ctrl_c();
+        if let Some(func) = sweep {
+            if runs.iter().any(|r| r.walk_forward.is_some()) {
+                exit_on_error::<()>(Err(anyhow!("walk-forward is not supported for sweeps")));
+            }
+            // queue all runs of the sweep before executing the first
+            let queued = runs
+                .into_iter()
+                .map(|run| {
+                    let split = exit_on_error(
+                        run.clone()
+                            .split_run_into_queue(&config.split)
+                            .context(format!("splitting {} failed", run.name)),
+                    );
+                    let ids = exit_on_error(
+                        journal
+                            .enqueue(&run.name, &split)
+                            .context("adding runs to the journal failed"),
+                    );
+                    (run, ids, split)
+                })
+                .collect::<Vec<_>>();
+            info!("sweeping {} {} indicators", queued.len(), func);
+
+            let mut results = Vec::new();
+            for (run, ids, split) in queued {
+                match run_journaled(&config, &journal, &run.name, &ids, &split, &cancel) {
+                    Ok(csv) => results.push((run, csv)),
+                    Err(e) if is_cancelled(&e) => break,
+                    Err(e) => error!("{} failed: {:?}", run.name, e),
+                }
+            }
+            let summary = exit_on_error(
+                summarize_sweep(func, &results).context("summarizing the sweep failed"),
+            );
+            for s in &summary {
+                println!(
+                    "{:>3} {:<30} best={:<12} median={:<12} passes={}",
+                    s.rank, s.indicator, s.best, s.median, s.passes
+                );
+            }
+            let csv = exit_on_error(get_reports_dir(&config).context("invalid reports dir"))
+                .join(format!("{}_sweep.csv", name));
+            exit_on_error(
+                write_sweep_summary(&summary, &csv).context("writing sweep summary failed"),
+            );
+            info!("sweep summary written to {:?}", csv);
+        } else if runs[0].walk_forward.is_some() {
+            let run = runs.pop().unwrap();
+            let report = exit_on_error(
+                walk_forward::run_walk_forward(&run, |r| {
+                    enqueue_and_run(&config, &journal, r.clone(), &cancel)
+                })
+                .context("walk-forward analysis failed"),
+            );
+            let dir = exit_on_error(get_reports_dir(&config).context("invalid reports dir"));
+            let csv = exit_on_error(
+                walk_forward::write_walk_forward_report(&report, &dir)
+                    .context("writing walk-forward report failed"),
+            );
+            info!(
+                "walk-forward report written to {:?}. out-of-sample profit: {} trades: {}",
+                csv, report.profit, report.trades
+            );
+        } else {
+            let run = runs.pop().unwrap();
+            exit_on_error(
+                enqueue_and_run(&config, &journal, run, &cancel).context("running backtest failed"),
+            );
+        }
+    }
+
+    // -------------
+    // Validate App
+    // -------------
+    if let Some(matches) = matches.subcommand_matches("validate") {
+        let input_file = matches.value_of("INPUT").unwrap();
+        let runs = exit_on_error(read_run_file(Path::new(input_file)).and_then(|f| f.expand()));
+        let mut valid = true;
+        for run in &runs {
+            match run.validate() {
+                Ok(()) => println!("{}: ok", run.name),
+                Err(e) => {
+                    valid = false;
+                    println!("{}: {}", run.name, e);
+                }
+            }
+        }
+        if !valid {
+            std::process::exit(1);
+        }
+    }
+
+    // -------------
+    // Best Passes App
+    // -------------
+    if let Some(matches) = matches.subcommand_matches("best") {
+        let mut params = RankParams::default();
+        if matches.is_present("METRIC") {
+            params.metric = value_t!(matches, "METRIC", RankMetric).unwrap_or_else(|e| e.exit());
+        }
+        if matches.is_present("MIN_TRADES") {
+            params.min_trades = value_t!(matches, "MIN_TRADES", u32).unwrap_or_else(|e| e.exit());
+        }
+        if matches.is_present("TOP") {
+            params.top = value_t!(matches, "TOP", usize).unwrap_or_else(|e| e.exit());
+        }
+        let run = exit_on_error(read_run_params(Path::new(matches.value_of("RUN").unwrap())));
+        let (header, rows) = exit_on_error(
+            results::read_results(Path::new(matches.value_of("REPORT").unwrap()))
+                .context("reading results failed"),
+        );
+
+        let best = exit_on_error(
+            best_passes(&header, &rows, &run.indi_set, &params)
+                .context("ranking the passes failed"),
+        );
+        for pass in &best {
+            println!(
+                "{:>3} pass {:>6} {:?}={:<12} trades={:<6} {:?}",
+                pass.rank,
+                pass.row.pass,
+                params.metric,
+                pass.value,
+                pass.row.trades,
+                pass.row
+                    .params
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+            );
+        }
+        let out = Path::new(matches.value_of("OUT").unwrap_or("config/indicator"));
+        let files = exit_on_error(
+            write_best_passes(&best, out, matches.is_present("FORCE"))
+                .context("writing indicator configs failed"),
+        );
+        for file in files {
+            info!("written {:?}", file);
+        }
+    }
+
+    // -------------
+    // Robustness App
+    // -------------
+    if let Some(matches) = matches.subcommand_matches("robustness") {
+        let mut params = RobustnessParams::default();
+        if matches.is_present("METRIC") {
+            params.metric = value_t!(matches, "METRIC", RankMetric).unwrap_or_else(|e| e.exit());
+        }
+        if matches.is_present("MIN_TRADES") {
+            params.min_trades = value_t!(matches, "MIN_TRADES", u32).unwrap_or_else(|e| e.exit());
+        }
+        if matches.is_present("RADIUS") {
+            params.radius = value_t!(matches, "RADIUS", usize).unwrap_or_else(|e| e.exit());
+        }
+        if matches.is_present("TOP") {
+            params.top = value_t!(matches, "TOP", usize).unwrap_or_else(|e| e.exit());
+        }
+        let run = exit_on_error(read_run_params(Path::new(matches.value_of("RUN").unwrap())));
+        let report = Path::new(matches.value_of("REPORT").unwrap());
+        let (header, rows) =
+            exit_on_error(results::read_results(report).context("reading results failed"));
+
+        let scores = exit_on_error(
+            score_robustness(&header, &rows, &run.indi_set, &params)
+                .context("scoring the passes failed"),
+        );
+        for s in &scores {
+            println!(
+                "{:>3} pass {:>6} mean={:<12} worst={:<12} value={:<12} neighbours={:<4} {:?}",
+                s.rank,
+                s.pass,
+                s.mean,
+                s.worst,
+                s.value,
+                s.neighbours,
+                s.region
+                    .iter()
+                    .map(|(min, max)| format!("{}..{}", min, max))
+                    .collect::<Vec<_>>()
+            );
+        }
+        let csv = report.with_file_name(format!(
+            "{}_robustness.csv",
+            report.file_stem().unwrap().to_string_lossy()
+        ));
+        exit_on_error(
+            write_robustness_csv(&header, &scores, &csv).context("writing robustness csv failed"),
+        );
+        info!("written {:?}", csv);
+    }
+
+    // -------------
+    // Pipeline App
+    // -------------
+    if let Some(matches) = matches.subcommand_matches("pipeline") {
+        let input_file = matches.value_of("INPUT").unwrap();
+        let params: PipelineParams = exit_on_error(
+            serde_any::from_file(input_file)
+                .map_err(|e| anyhow!("reading {} failed: {}", input_file, e)),
+        );
+        let base = exit_on_error(read_run_params(&params.run));
+        let dir = pipeline::pipeline_dir(
+            &exit_on_error(get_reports_dir(&config).context("invalid reports dir")),
+            &params,
+        );
+
+        let journal = open_journal(&config);
+        let cancel = cancel_on_ctrl_c();
+        let stages = exit_on_error(
+            pipeline::run_pipeline(&params, &base, &dir, |r| {
+                enqueue_and_run(&config, &journal, r.clone(), &cancel)
+            })
+            .context("pipeline failed"),
+        );
+        for s in &stages {
+            for (i, c) in s.kept.iter().enumerate() {
+                println!(
+                    "stage {} {:<10} {:>2} value={:<12} {:?}",
+                    s.stage,
+                    s.func,
+                    i + 1,
+                    c.value,
+                    c.file
+                );
+            }
+        }
+        info!("stage results written to {:?}", dir);
+    }
 
-        // let runs = run.split_run_into_queue();
-        let runs = vec![run];
-        backtest_runner::execute_run_queue(&config, &runs).expect("running queue failed");
+    // -------------
+    // Queue App
+    // -------------
+    if let Some(matches) = matches.subcommand_matches("queue") {
+        let journal = open_journal(&config);
+        let entry_id = |m: &clap::ArgMatches| -> journal::EntryId {
+            value_t!(m, "ID", journal::EntryId).unwrap_or_else(|e| e.exit())
+        };
+
+        match matches.subcommand() {
+            ("list", _) => {
+                for e in journal.list() {
+                    println!(
+                        "{:>5} {:<8} {:<30} {:<30} {}",
+                        e.id,
+                        format!("{:?}", e.state),
+                        e.job,
+                        e.run.name,
+                        e.run.symbols.join(" ")
+                    );
+                }
+            }
+            ("retry", Some(m)) => {
+                exit_on_error(journal.retry(entry_id(m)).context("retrying run failed"));
+            }
+            ("drop", Some(m)) => {
+                exit_on_error(
+                    journal
+                        .drop_entry(entry_id(m))
+                        .context("dropping run failed"),
+                );
+            }
+            ("resume", _) => {
+                let cancel = cancel_on_ctrl_c();
+                for (name, entries) in journal.pending() {
+                    let ids = entries.iter().map(|e| e.id).collect::<Vec<_>>();
+                    let runs = entries.into_iter().map(|e| e.run).collect::<Vec<_>>();
+                    exit_on_error(
+                        run_journaled(&config, &journal, &name, &ids, &runs, &cancel)
+                            .context("running queue failed"),
+                    );
+                }
+            }
+            _ => unreachable!(),
+        }
     }
 
     Ok(())
 }
 
+// errors of the CLI end it with the message instead of a panic
+fn exit_on_error<T>(result: anyhow::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1)
+    })
+}
+
+// the queue of a run with the number of passes of every run in it. With files also the report
+// path, terminal.ini and .set file of every run
+fn print_plan(plan: &Plan, files: bool) {
+    for run in &plan.runs {
+        println!(
+            "{:<40} passes={:<12} {}",
+            run.name,
+            run.passes,
+            run.symbols.join(" ")
+        );
+        if files {
+            println!("report: {}", run.report.display());
+            println!("terminal.ini:\n{}\n", run.terminal_ini);
+            println!(".set:\n{}\n", run.params);
+        }
+    }
+    println!(
+        "{}: {} run(s) with {} passes",
+        plan.name,
+        plan.runs.len(),
+        plan.passes
+    );
+}
+
+// never overwrites an existing file
+fn write_new_yaml<T: serde::Serialize>(file: &Path, value: &T) -> anyhow::Result<()> {
+    ensure!(!file.exists(), "{:?} already exists", file);
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    serde_any::to_file(file, value).map_err(|e| anyhow!("writing {:?} failed: {:?}", file, e))
+}
+
+fn read_run_file(file: &Path) -> anyhow::Result<RunParamsFile> {
+    serde_any::from_file(file).map_err(|e| anyhow!("reading {:?} failed: {}", file, e))
+}
+
+// the run config with all its indicators
+fn read_run_params(file: &Path) -> anyhow::Result<RunParams> {
+    RunParams::try_from(read_run_file(file)?).context(format!("invalid run config {:?}", file))
+}
+
+fn open_journal(config: &CommonParams) -> Arc<RunJournal> {
+    Arc::new(exit_on_error(
+        RunJournal::open(&config.journal)
+            .context(format!("opening journal {:?} failed", config.journal)),
+    ))
+}
+
+// The first Ctrl-C stops the runs of the CLI, they continue when the run is started again. The
+// second one ends backtestd right away.
+fn cancel_on_ctrl_c() -> CancelToken {
+    let cancel = CancelToken::default();
+    let token = cancel.clone();
+    // the runs block the runtime of main
+    std::thread::spawn(move || {
+        let mut sys = actix_rt::System::new("ctrl-c");
+        if sys.block_on(actix_rt::signal::ctrl_c()).is_ok() {
+            warn!("cancelling the runs. press Ctrl-C again to exit right away");
+            token.cancel();
+            let _ = sys.block_on(actix_rt::signal::ctrl_c());
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
+// split a logical run into a queue, add it to the journal and execute it
+fn enqueue_and_run(
+    config: &CommonParams,
+    journal: &Arc<RunJournal>,
+    run: RunParams,
+    cancel: &CancelToken,
+) -> anyhow::Result<PathBuf> {
+    run.validate()
+        .context(format!("invalid run {}", run.name))?;
+    let name = run.name.clone();
+    let runs = run.split_run_into_queue(&config.split)?;
+    let ids = journal
+        .enqueue(&name, &runs)
+        .context("adding runs to the journal failed")?;
+    run_journaled(config, journal, &name, &ids, &runs, cancel)
+}
+
+// returns the merged csv
+fn run_journaled(
+    config: &CommonParams,
+    journal: &Arc<RunJournal>,
+    name: &str,
+    ids: &[journal::EntryId],
+    runs: &[RunParams],
+    cancel: &CancelToken,
+) -> anyhow::Result<PathBuf> {
+    let outputs = journal::execute_journaled(config, journal, ids, runs, cancel, |_, _| ())
+        .context("running queue failed")?;
+    for o in &outputs {
+        if !o.diagnostics.warnings.is_empty() || !o.diagnostics.errors.is_empty() {
+            warn!("{:?}: {:#?}", o.csv, o.diagnostics);
+        }
+    }
+    let merged = backtest_runner::merge_queue_results(config, name, runs, &outputs)
+        .context("merging results failed")?;
+    info!("results written to {:?}", merged);
+    Ok(merged)
+}
+
 async fn server(config: CommonParams) -> std::io::Result<()> {
-    return HttpServer::new(move || {
+    let jobs = JobQueue::with_journal(open_journal(&config));
+    jobs.resume_pending();
+    jobs.start_worker(config.clone());
+
+    HttpServer::new(move || {
             ActixApp::new()
                 // enable logger
                 .wrap(middleware::Logger::default())
                 .data(config.clone())
+                .data(jobs.clone())
                 .service(web::resource("/run").route(web::post().to(backtest_run)))
+                .service(web::resource("/plan").route(web::post().to(api::plan_run)))
+                .service(
+                    web::resource("/results/{run}").route(web::get().to(api::query_results)),
+                )
+                .service(web::resource("/jobs").route(web::post().to(api::submit_job)))
+                .service(
+                    web::resource("/jobs/{id}")
+                        .route(web::get().to(api::job_status))
+                        .route(web::delete().to(api::cancel_job)),
+                )
+                .service(
+                    web::resource("/jobs/{id}/results").route(web::get().to(api::job_results)),
+                )
+                .service(
+                    web::resource("/jobs/{id}/events").route(web::get().to(api::job_events)),
+                )
+                .service(web::resource("/jobs/{id}/files").route(web::get().to(api::job_files)))
+                .service(
+                    web::resource("/jobs/{id}/files/{file}").route(web::get().to(api::job_file)),
+                )
+                .service(web::resource("/jobs/{id}/best").route(web::get().to(api::job_best)))
+                .service(
+                    web::resource("/jobs/{id}/robustness")
+                        .route(web::get().to(api::job_robustness)),
+                )
         })
         // start http server
         .bind("0.0.0.0:12311")?
         .run()
-        .await;
+        .await
 }
 
 async fn backtest_run(
-    data: web::Json<RunParams>,
+    data: web::Json<serde_json::Value>,
     config: web::Data<CommonParams>,
 ) -> Result<HttpResponse, ActixError> {
-    let run = data.into_inner();
+    let run = api::parse_run(data.into_inner())?;
     let config = config.into_inner();
     info!("running backtest with common: {:?}\nrun:{:?}", config, run);
 
-    // let runs = run.split_run_into_queue();
-    let runs = vec![run];
-    backtest_runner::execute_run_queue(&config, &runs).map_err(|e| ErrorInternalServerError(e))?;
+    let name = run.name.clone();
+    let runs = run
+        .split_run_into_queue(&config.split)
+        .map_err(|e| ErrorBadRequest(format!("{:#}", e)))?;
+    let outputs =
+        backtest_runner::execute_run_queue(&config, &runs).map_err(ErrorInternalServerError)?;
+    let merged = backtest_runner::merge_queue_results(&config, &name, &runs, &outputs)
+        .map_err(|e| ErrorInternalServerError(format!("{:#}", e)))?;
 
-    Ok(HttpResponse::Ok().json(
-        get_csv_filenames_from_queue(&config, &runs), // .map_err(|e| ErrorInternalServerError(e))?,
-    ))
+    // still a list of csv files, but only the merged one of all runs of the queue
+    Ok(HttpResponse::Ok().json(vec![merged]))
 }
@@ -6,6 +6,9 @@ use std::path::PathBuf;
 pub struct CommonParams {
     pub params_file: String,
     pub wine: bool,
+    // WINEPREFIX of the terminal. A hanging terminal is killed with all processes of its prefix
+    #[serde(default)]
+    pub wineprefix: Option<PathBuf>,
     pub terminal_exe: PathBuf,
     pub workdir: PathBuf,
     pub reports: PathBuf,
@@ -20,6 +23,31 @@ pub struct CommonParams {
     pub currency: String,
     pub leverage: u16,
     pub execution_mode: u8,
+    // additional portable terminal installations to run the queue on in parallel.
+    // if empty the terminal configured above is used
+    #[serde(default)]
+    pub terminals: Vec<TerminalInstance>,
+    #[serde(default)]
+    pub split: SplitParams,
+    #[serde(default)]
+    pub timeouts: TimeoutParams,
+    // json-lines file the queue is persisted to
+    #[serde(default = "default_journal")]
+    pub journal: PathBuf,
+}
+
+pub fn default_journal() -> PathBuf {
+    PathBuf::from("backtestd-queue.jsonl")
+}
+
+// a single terminal installation with its own data folder
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TerminalInstance {
+    pub terminal_exe: PathBuf,
+    pub workdir: PathBuf,
+    pub login: String,
+    #[serde(default)]
+    pub wineprefix: Option<PathBuf>,
 }
 
 impl CommonParams {
@@ -30,6 +58,48 @@ impl CommonParams {
         params_path
     }
 
+    // one CommonParams per terminal installation. All files of a run (terminal.ini, .set, report
+    // and log) are placed relative to the workdir, so the instances don't interfere.
+    pub fn instances(&self) -> Vec<CommonParams> {
+        if self.terminals.is_empty() {
+            return vec![self.clone()];
+        }
+        self.terminals
+            .iter()
+            .map(|t| CommonParams {
+                terminal_exe: t.terminal_exe.clone(),
+                workdir: t.workdir.clone(),
+                login: t.login.clone(),
+                wineprefix: t.wineprefix.clone().or_else(|| self.wineprefix.clone()),
+                terminals: Vec::new(),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    // Killing a terminal under wine kills its whole wine prefix, so terminals that run in parallel
+    // under wine need a prefix each.
+    pub fn check_wineprefixes(&self) -> Result<()> {
+        let instances = self.instances();
+        if !self.wine || instances.len() < 2 {
+            return Ok(());
+        }
+        let mut prefixes = Vec::new();
+        for i in &instances {
+            let prefix = i.wineprefix.as_ref().context(format!(
+                "terminal {:?} needs its own wineprefix to run next to the other terminals",
+                i.terminal_exe
+            ))?;
+            ensure!(
+                !prefixes.contains(&prefix),
+                "wineprefix {:?} is used by more than one terminal",
+                prefix
+            );
+            prefixes.push(prefix);
+        }
+        Ok(())
+    }
+
     pub fn to_config(&self) -> String {
         format!(
             "
@@ -64,6 +134,7 @@ ExecutionMode={exec_mode}",
         CommonParams {
             params_file: "expert_params.set".to_string(),
             wine: false,
+            wineprefix: None,
             terminal_exe: PathBuf::from(r"C:\terminal64.exe"),
             workdir: PathBuf::from(r"C:\workdir"),
             reports: PathBuf::from("reports"),
@@ -78,6 +149,79 @@ ExecutionMode={exec_mode}",
             currency: "USD".to_string(),
             leverage: 100,
             execution_mode: 0,
+            terminals: Vec::new(),
+            split: Default::default(),
+            timeouts: Default::default(),
+            journal: PathBuf::from("backtestd-queue.jsonl"),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn instances_test() {
+        let mut common = CommonParams::_new_test();
+        assert_eq!(common.instances(), vec![common.clone()]);
+
+        common.terminals = vec![
+            TerminalInstance {
+                terminal_exe: PathBuf::from(r"C:\mt5_1\terminal64.exe"),
+                workdir: PathBuf::from(r"C:\mt5_1"),
+                login: "1".to_string(),
+                wineprefix: None,
+            },
+            TerminalInstance {
+                terminal_exe: PathBuf::from(r"C:\mt5_2\terminal64.exe"),
+                workdir: PathBuf::from(r"C:\mt5_2"),
+                login: "2".to_string(),
+                wineprefix: None,
+            },
+        ];
+        let instances = common.instances();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].workdir, PathBuf::from(r"C:\mt5_1"));
+        assert_eq!(instances[0].login, "1");
+        assert_eq!(
+            instances[1].terminal_exe,
+            PathBuf::from(r"C:\mt5_2\terminal64.exe")
+        );
+        assert_eq!(instances[1].login, "2");
+        assert_eq!(instances[1].expert, common.expert);
+        assert!(instances.iter().all(|i| i.terminals.is_empty()));
+        assert_ne!(instances[0].params_path(), instances[1].params_path());
+    }
+
+    #[test]
+    fn check_wineprefixes_test() {
+        let mut common = CommonParams::_new_test();
+        common.wine = true;
+        assert!(common.check_wineprefixes().is_ok());
+
+        common.terminals = (1..=2)
+            .map(|i| TerminalInstance {
+                terminal_exe: PathBuf::from(format!(r"C:\mt5_{}\terminal64.exe", i)),
+                workdir: PathBuf::from(format!(r"C:\mt5_{}", i)),
+                login: i.to_string(),
+                wineprefix: None,
+            })
+            .collect();
+        assert!(common.check_wineprefixes().is_err());
+        // the prefix of the config is shared by both terminals
+        common.wineprefix = Some(PathBuf::from("/home/mt5/.wine"));
+        assert!(common.check_wineprefixes().is_err());
+
+        common.terminals[1].wineprefix = Some(PathBuf::from("/home/mt5/.wine_2"));
+        assert!(common.check_wineprefixes().is_ok());
+        assert_eq!(
+            common.instances()[0].wineprefix,
+            Some(PathBuf::from("/home/mt5/.wine"))
+        );
+
+        common.wine = false;
+        common.terminals[1].wineprefix = None;
+        assert!(common.check_wineprefixes().is_ok());
+    }
+}
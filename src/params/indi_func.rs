@@ -10,3 +10,42 @@ pub enum IndiFunc {
     Continue,
     Exit,
 }
+
+impl std::str::FromStr for IndiFunc {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use IndiFunc::*;
+        Ok(match s {
+            "Confirm" => Confirm,
+            "Confirm2" => Confirm2,
+            "Confirm3" => Confirm3,
+            "Baseline" => Baseline,
+            "Volume" => Volume,
+            "Continue" => Continue,
+            "Exit" => Exit,
+            _ => bail!("unknown indicator function: {}", s),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_str_test() {
+        for func in &[
+            IndiFunc::Confirm,
+            IndiFunc::Confirm2,
+            IndiFunc::Confirm3,
+            IndiFunc::Baseline,
+            IndiFunc::Volume,
+            IndiFunc::Continue,
+            IndiFunc::Exit,
+        ] {
+            assert_eq!(func.to_string().parse::<IndiFunc>().unwrap(), *func);
+        }
+        assert!("Trades".parse::<IndiFunc>().is_err());
+    }
+}
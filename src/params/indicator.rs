@@ -1,6 +1,8 @@
+use super::input::Input;
 use super::signal_class::SignalClass;
 use crate::params::indi_func::IndiFunc;
-use bigdecimal::BigDecimal;
+use anyhow::{Context, Result};
+use bigdecimal::{BigDecimal, Zero};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, PartialOrd, Serialize, Deserialize, Clone)]
@@ -8,7 +10,7 @@ pub struct Indicator {
     pub name: String,
     pub filename: Option<String>,
     pub class: SignalClass,
-    pub inputs: Vec<Vec<BigDecimal>>,
+    pub inputs: Vec<Input>,
     pub buffers: Option<Vec<u8>>,
     pub params: Option<Vec<BigDecimal>>,
     pub shift: u8,
@@ -34,7 +36,7 @@ impl Indicator {
             self.inputs
                 .iter()
                 .enumerate()
-                .map(|(i, input)| format!("input{}={}", i, input_param_str(input))),
+                .map(|(i, input)| format!("input{}={}", i, input.to_param_str())),
         );
         if let Some(buffers) = &self.buffers {
             res.extend(
@@ -55,71 +57,75 @@ impl Indicator {
         res
     }
 
-    pub fn count_inputs_crossed(&self) -> u64 {
-        let lengths = self.count_input_length();
-        if lengths.len() == 0 {
-            return 0u64;
-        }
-        lengths.iter().fold(1u64, |prod, x| prod * x)
+    // saturates at u64::MAX
+    pub fn count_inputs_crossed(&self) -> Result<u64> {
+        Ok(self
+            .count_input_length()?
+            .iter()
+            .fold(1u64, |prod, x| prod.saturating_mul(*x)))
     }
 
-    pub fn count_input_length(&self) -> Vec<u64> {
-        use bigdecimal::ToPrimitive;
+    pub fn count_input_length(&self) -> Result<Vec<u64>> {
         self.inputs
             .iter()
-            // .filter(|i| i.len() == 3 || i.len() == 4)
-            .map(|i| match i.len() {
-                3 => (((i[1].to_f32().unwrap() - &i[0].to_f32().unwrap()) + 1f32)
-                    / &i[2].to_f32().unwrap())
-                    .floor() as u64,
-                4 => (((i[2].to_f32().unwrap() - &i[1].to_f32().unwrap()) + 1f32)
-                    / &i[3].to_f32().unwrap())
-                    .floor() as u64,
-                _ => 1u64,
-            })
+            .enumerate()
+            .map(|(i, input)| input.count().context(format!("input{}", i)))
             .collect()
     }
 
     pub fn slice_longest_input(&self) -> Option<Vec<Self>> {
-        use std::cmp::Ordering;
-
-        let index_of_max: Option<usize> = self
+        let (i, len) = self
             .count_input_length()
-            .iter()
+            .ok()?
+            .into_iter()
             .enumerate()
-            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-            .map(|(index, _)| index);
-
-        if let Some(i) = index_of_max {
-            debug!("slicing longest input: {:?}", self.inputs[i]);
-            let mut new_indis = vec![self.clone(), self.clone()];
-            let mut start_idx = 0;
-            if self.inputs[i].len() == 4 {
-                start_idx = 1;
-            }
-            let start = &self.inputs[i][start_idx];
-            let stop = &self.inputs[i][start_idx + 1];
-            let step = &self.inputs[i][start_idx + 2];
-
-            let new_start = start + (stop - start) / 2;
-            new_indis[0].inputs[i][start_idx + 1] = &new_start - step;
-            new_indis[1].inputs[i][start_idx] = new_start;
-            return Some(new_indis);
+            .max_by_key(|(_, len)| *len)?;
+        if len < 2 {
+            return None;
         }
 
-        None
+        debug!("slicing longest input: {:?}", self.inputs[i]);
+        let halves = self.inputs[i].chunks(2).ok()?;
+        Some(
+            halves
+                .into_iter()
+                .map(|input| {
+                    let mut indi = self.clone();
+                    indi.inputs[i] = input;
+                    indi
+                })
+                .collect(),
+        )
+    }
+
+    // the inverse of slice_longest_input: widen the ranges of the inputs to cover other
+    pub fn merge_ranges(&mut self, other: &Indicator) {
+        for (input, other) in self.inputs.iter_mut().zip(other.inputs.iter()) {
+            if let (Some((start, stop, step)), Some((other_start, other_stop, _))) =
+                (input.range.bounds_mut(), other.range.bounds())
+            {
+                // a descending range starts at its largest value
+                let descending = *step < BigDecimal::zero();
+                if (!descending && other_start < start) || (descending && other_start > start) {
+                    *start = other_start.clone();
+                }
+                if (!descending && other_stop > stop) || (descending && other_stop < stop) {
+                    *stop = other_stop.clone();
+                }
+            }
+        }
     }
 
     pub fn _new_test(func: IndiFunc, input_variant: i32) -> Self {
-        use crate::params::_vec_vec_to_bigdecimal;
+        use crate::params::_vec_vec_to_inputs;
 
         Indicator {
             name: format!("{:?}", func),
             filename: Some(format!("{:?}", func)),
             shift: 0,
             inputs: match input_variant {
-                1 => _vec_vec_to_bigdecimal(vec![vec![1.]]),
-                2 => _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.]]),
+                1 => _vec_vec_to_inputs(vec![vec![1.]]),
+                2 => _vec_vec_to_inputs(vec![vec![10., 20., 1.]]),
                 _ => Vec::new(),
             },
             buffers: None,
@@ -129,24 +135,11 @@ impl Indicator {
     }
 }
 
-fn input_param_str(input: &Vec<BigDecimal>) -> String {
-    match input.len() {
-        1 => format!("{:.5}||0||0||0||N", input[0]),
-        3 => format!("0||{:.5}||{:.5}||{:.5}||Y", input[0], input[2], input[1]),
-        4 => format!(
-            "{:.5}||{:.5}||{:.5}||{:.5}||Y",
-            input[0], input[1], input[3], input[2]
-        ),
-        e => panic!("wrong length of indicator params input: {}", e),
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::params::_vec_to_bigdecimal;
-    use crate::params::_vec_vec_to_bigdecimal;
     use crate::params::signal_class::SignalClass::*;
+    use crate::params::{_vec_to_bigdecimal, _vec_to_input, _vec_vec_to_inputs};
     use glob::glob;
 
     #[test]
@@ -163,7 +156,7 @@ mod test {
 
         assert_eq!(
             indi.to_param_string_vec(),
-            vec!["Indicator=ama", "SignalClass=0", "Shift=0"]
+            ["Indicator=ama", "SignalClass=0", "Shift=0"]
                 .iter()
                 .map(|s| s.to_string())
                 .collect::<Vec<String>>()
@@ -176,7 +169,7 @@ mod test {
             indi.filename = Some("ama.ex5".to_string());
             assert_eq!(
                 indi.to_param_string_vec(),
-                vec!["Indicator=ama", "SignalClass=0", "Shift=0"]
+                ["Indicator=ama", "SignalClass=0", "Shift=0"]
                     .iter()
                     .map(|s| s.to_string())
                     .collect::<Vec<String>>()
@@ -185,7 +178,7 @@ mod test {
             indi.class = ZeroLineCross;
             assert_eq!(
                 indi.to_param_string_vec(),
-                vec!["Indicator=ama.ex5", "SignalClass=1", "Shift=0"]
+                ["Indicator=ama.ex5", "SignalClass=1", "Shift=0"]
                     .iter()
                     .map(|s| s.to_string())
                     .collect::<Vec<String>>()
@@ -197,7 +190,7 @@ mod test {
             indi.buffers = Some(vec![1u8]);
             assert_eq!(
                 indi.to_param_string_vec(),
-                vec!["Indicator=ama", "SignalClass=0", "Shift=0", "buffer0=1"]
+                ["Indicator=ama", "SignalClass=0", "Shift=0", "buffer0=1"]
                     .iter()
                     .map(|s| s.to_string())
                     .collect::<Vec<String>>()
@@ -209,7 +202,7 @@ mod test {
             indi.params = Some(_vec_to_bigdecimal(vec![1.]));
             assert_eq!(
                 indi.to_param_string_vec(),
-                vec![
+                [
                     "Indicator=ama",
                     "SignalClass=0",
                     "Shift=0",
@@ -223,7 +216,7 @@ mod test {
             indi.params = Some(_vec_to_bigdecimal(vec![1., 4.55]));
             assert_eq!(
                 indi.to_param_string_vec(),
-                vec![
+                [
                     "Indicator=ama",
                     "SignalClass=0",
                     "Shift=0",
@@ -239,16 +232,16 @@ mod test {
         indi.shift = 7;
         assert_eq!(
             indi.to_param_string_vec(),
-            vec!["Indicator=ama", "SignalClass=0", "Shift=7",]
+            ["Indicator=ama", "SignalClass=0", "Shift=7",]
                 .iter()
                 .map(|s| s.to_string())
                 .collect::<Vec<String>>()
         );
 
-        indi.inputs.push(_vec_to_bigdecimal(vec![3.]));
+        indi.inputs.push(_vec_to_input(vec![3.]));
         assert_eq!(
             indi.to_param_string_vec(),
-            vec![
+            [
                 "Indicator=ama",
                 "SignalClass=0",
                 "Shift=7",
@@ -259,10 +252,10 @@ mod test {
             .collect::<Vec<String>>()
         );
 
-        indi.inputs.push(_vec_to_bigdecimal(vec![4.]));
+        indi.inputs.push(_vec_to_input(vec![4.]));
         assert_eq!(
             indi.to_param_string_vec(),
-            vec![
+            [
                 "Indicator=ama",
                 "SignalClass=0",
                 "Shift=7",
@@ -274,10 +267,10 @@ mod test {
             .collect::<Vec<String>>()
         );
 
-        indi.inputs.push(_vec_to_bigdecimal(vec![10., 200., 0.5]));
+        indi.inputs.push(_vec_to_input(vec![10., 200., 0.5]));
         assert_eq!(
             indi.to_param_string_vec(),
-            vec![
+            [
                 "Indicator=ama",
                 "SignalClass=0",
                 "Shift=7",
@@ -290,11 +283,10 @@ mod test {
             .collect::<Vec<String>>()
         );
 
-        indi.inputs
-            .push(_vec_to_bigdecimal(vec![15., 10., 20., 0.5]));
+        indi.inputs.push(_vec_to_input(vec![15., 10., 20., 0.5]));
         assert_eq!(
             indi.to_param_string_vec(),
-            vec![
+            [
                 "Indicator=ama",
                 "SignalClass=0",
                 "Shift=7",
@@ -311,7 +303,7 @@ mod test {
         indi.class = ZeroLineCross;
         assert_eq!(
             indi.to_param_string_vec(),
-            vec![
+            [
                 "Indicator=ama",
                 "SignalClass=1",
                 "Shift=7",
@@ -346,19 +338,27 @@ mod test {
             class: Preset,
         };
 
-        assert_eq!(indi.count_inputs_crossed(), 0);
+        // an indicator without inputs is run once
+        assert_eq!(indi.count_inputs_crossed().unwrap(), 1);
 
-        indi.inputs = _vec_vec_to_bigdecimal(vec![vec![1.]]);
-        assert_eq!(indi.count_inputs_crossed(), 1);
+        indi.inputs = _vec_vec_to_inputs(vec![vec![1.]]);
+        assert_eq!(indi.count_inputs_crossed().unwrap(), 1);
 
-        indi.inputs.push(_vec_to_bigdecimal(vec![11., 15., 1.]));
-        assert_eq!(indi.count_inputs_crossed(), 5);
+        indi.inputs.push(_vec_to_input(vec![11., 15., 1.]));
+        assert_eq!(indi.count_inputs_crossed().unwrap(), 5);
 
-        indi.inputs
-            .push(_vec_to_bigdecimal(vec![15., 11., 20., 0.5]));
-        assert_eq!(indi.count_inputs_crossed(), 100);
+        indi.inputs.push(_vec_to_input(vec![15., 11., 20., 0.5]));
+        assert_eq!(indi.count_inputs_crossed().unwrap(), 95);
 
-        indi.slice_longest_input();
+        indi.inputs.push(_vec_to_input(vec![1., 100000., 0.0001]));
+        indi.inputs.push(_vec_to_input(vec![1., 100000., 0.0001]));
+        assert_eq!(indi.count_inputs_crossed().unwrap(), u64::MAX);
+
+        indi.inputs.push(_vec_to_input(vec![1., 10., 0.]));
+        let err = indi.count_inputs_crossed().unwrap_err();
+        assert_eq!(format!("{:#}", err), "input5: the step is zero");
+        // nor can it be sliced
+        assert!(indi.slice_longest_input().is_none());
     }
 
     #[test]
@@ -375,28 +375,28 @@ mod test {
 
         assert!(indi.slice_longest_input().is_none());
 
-        indi.inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.]]);
+        indi.inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.]]);
         let mut indis = vec![indi.clone(), indi.clone()];
-        indis[0].inputs = _vec_vec_to_bigdecimal(vec![vec![10., 14., 1.]]);
-        indis[1].inputs = _vec_vec_to_bigdecimal(vec![vec![15., 20., 1.]]);
+        indis[0].inputs = _vec_vec_to_inputs(vec![vec![10., 14., 1.]]);
+        indis[1].inputs = _vec_vec_to_inputs(vec![vec![15., 20., 1.]]);
         assert_eq!(indi.slice_longest_input(), Some(indis));
 
-        indi.inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.], vec![10., 20., 0.5]]);
+        indi.inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.], vec![10., 20., 0.5]]);
         let mut indis = vec![indi.clone(), indi.clone()];
-        indis[0].inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.], vec![10., 14.5, 0.5]]);
-        indis[1].inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.], vec![15., 20., 0.5]]);
+        indis[0].inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.], vec![10., 14.5, 0.5]]);
+        indis[1].inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.], vec![15., 20., 0.5]]);
         assert_eq!(indi.slice_longest_input(), Some(indis));
 
-        indi.inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.], vec![20., 10., -0.5]]);
+        indi.inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.], vec![20., 10., -0.5]]);
         let mut indis = vec![indi.clone(), indi.clone()];
-        indis[0].inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.], vec![20., 15.5, -0.5]]);
-        indis[1].inputs = _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.], vec![15., 10., -0.5]]);
+        indis[0].inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.], vec![20., 15.5, -0.5]]);
+        indis[1].inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.], vec![15., 10., -0.5]]);
         assert_eq!(indi.slice_longest_input(), Some(indis));
 
-        indi.inputs = _vec_vec_to_bigdecimal(vec![vec![15., 5., 25., 1.]]);
+        indi.inputs = _vec_vec_to_inputs(vec![vec![15., 5., 25., 1.]]);
         let mut indis = vec![indi.clone(), indi.clone()];
-        indis[0].inputs = _vec_vec_to_bigdecimal(vec![vec![15., 5., 14., 1.]]);
-        indis[1].inputs = _vec_vec_to_bigdecimal(vec![vec![15., 15., 25., 1.]]);
+        indis[0].inputs = _vec_vec_to_inputs(vec![vec![15., 5., 14., 1.]]);
+        indis[1].inputs = _vec_vec_to_inputs(vec![vec![15., 15., 25., 1.]]);
         assert_eq!(indi.slice_longest_input(), Some(indis));
     }
 }
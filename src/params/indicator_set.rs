@@ -5,6 +5,7 @@ use super::indicator::Indicator;
 
 use super::to_param_string::ToParamString;
 
+use anyhow::{Context, Result};
 use derive_more::{Constructor, Deref, DerefMut, From, IntoIterator};
 use std::collections::HashMap;
 
@@ -33,33 +34,37 @@ impl IndicatorSet {
     pub fn to_param_string_vec(&self) -> Vec<String> {
         self.clone()
             .into_iter()
-            .map(|(func, indi)| {
+            .flat_map(|(func, indi)| {
                 // the hashmap <func, indicator>
                 indi.to_param_string_vec()
                     .iter()
                     .map(|indi_param| func.to_string().to_owned() + "_" + indi_param)
                     .collect::<Vec<String>>()
             })
-            .flatten()
             .collect()
     }
 
-    pub fn count_inputs_crossed(&self) -> u64 {
-        let lengths = self.count_input_length();
-        if lengths.len() == 0 {
-            return 0u64;
-        }
-        lengths.iter().fold(1u64, |prod, (_, x)| prod * x)
+    // saturates at u64::MAX
+    pub fn count_inputs_crossed(&self) -> Result<u64> {
+        Ok(self
+            .count_input_length()?
+            .values()
+            .fold(1u64, |prod, x| prod.saturating_mul(*x)))
     }
 
-    pub fn count_input_length(&self) -> HashMap<IndiFunc, u64> {
+    pub fn count_input_length(&self) -> Result<HashMap<IndiFunc, u64>> {
         self.iter()
-            .map(|(f, i)| (f.clone(), i.count_inputs_crossed()))
+            .map(|(f, i)| {
+                let count = i
+                    .count_inputs_crossed()
+                    .context(format!("{} indicator {}", f, i.name))?;
+                Ok((*f, count))
+            })
             .collect()
     }
 
     pub fn slice_longest_input(&self) -> Option<Vec<Self>> {
-        let lengths = self.count_input_length();
+        let lengths = self.count_input_length().ok()?;
         let longest = lengths.iter().max_by(|(_, a), (_, b)| a.cmp(b));
 
         if let Some((f, l)) = longest {
@@ -67,9 +72,9 @@ impl IndicatorSet {
 
             // get the longest indicator and slice it
             let mut new_sets = Vec::with_capacity(2);
-            for new_indi in self.get(&f).unwrap().slice_longest_input().unwrap() {
+            for new_indi in self.get(f).unwrap().slice_longest_input()? {
                 let mut new_set = self.clone(); // clone the set
-                let _ = new_set.insert(f.clone(), new_indi);
+                let _ = new_set.insert(*f, new_indi);
                 new_sets.push(new_set);
             }
             return Some(new_sets);
@@ -79,18 +84,35 @@ impl IndicatorSet {
         None
     }
 
-    pub fn slice_recursive(self, target_length: u64) -> Vec<Self> {
-        let cnt = self.count_inputs_crossed();
+    // slices the set until every slice has less than target_length combinations
+    pub fn slice_recursive(self, target_length: u64) -> Result<Vec<Self>> {
+        let cnt = self.count_inputs_crossed()?;
         if cnt < target_length {
             debug!("returning from slicing at length {}", cnt);
-            return vec![self];
+            return Ok(vec![self]);
         }
-        self.slice_longest_input()
-            .unwrap()
-            .into_iter()
-            .map(|s| s.slice_recursive(target_length))
-            .flatten()
-            .collect()
+        let mut sets = Vec::new();
+        for s in self.slice_longest_input().context(format!(
+            "{} combinations can't be sliced below {}",
+            cnt, target_length
+        ))? {
+            sets.extend(s.slice_recursive(target_length)?);
+        }
+        Ok(sets)
+    }
+
+    // the inverse of slice_recursive: the set with the ranges of all slices
+    pub fn merge_ranges(sets: &[&IndicatorSet]) -> Option<Self> {
+        let (first, rest) = sets.split_first()?;
+        let mut merged = (*first).clone();
+        for set in rest {
+            for (func, indi) in merged.iter_mut() {
+                if let Some(other) = set.get(func) {
+                    indi.merge_ranges(other);
+                }
+            }
+        }
+        Some(merged)
     }
 
     pub fn _new_test(num: usize) -> Self {
@@ -109,7 +131,7 @@ impl IndicatorSet {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::params::_vec_vec_to_bigdecimal;
+    use crate::params::_vec_vec_to_inputs;
     use crate::params::indi_func::IndiFunc::*;
     use crate::params::signal_class::SignalClass::*;
     use std::collections::HashMap;
@@ -154,17 +176,18 @@ mod test {
                 class: Preset,
             },
         );
+        let mut params = set.to_param_string_vec();
+        params.sort();
         assert_eq!(
-            vec![
-                "Confirm_Indicator=ama",
-                "Confirm_SignalClass=0",
-                "Confirm_Shift=0",
+            params,
+            [
                 "Baseline_Indicator=ma",
+                "Baseline_Shift=0",
                 "Baseline_SignalClass=0",
-                "Baseline_Shift=0"
+                "Confirm_Indicator=ama",
+                "Confirm_Shift=0",
+                "Confirm_SignalClass=0",
             ]
-            .sort(),
-            set.to_param_string_vec().sort()
         );
     }
 
@@ -177,7 +200,7 @@ mod test {
                     name: "ama".to_string(),
                     filename: None,
                     shift: 0,
-                    inputs: _vec_vec_to_bigdecimal(vec![vec![10., 20., 1.]]),
+                    inputs: _vec_vec_to_inputs(vec![vec![10., 20., 1.]]),
                     buffers: None,
                     params: None,
                     class: Preset,
@@ -189,7 +212,7 @@ mod test {
                     name: "ama2".to_string(),
                     filename: None,
                     shift: 0,
-                    inputs: _vec_vec_to_bigdecimal(vec![vec![10., 20., 0.5]]),
+                    inputs: _vec_vec_to_inputs(vec![vec![10., 20., 0.5]]),
                     buffers: None,
                     params: None,
                     class: Preset,
@@ -205,9 +228,44 @@ mod test {
 
         let mut new_set = vec![set.clone(), set.clone()];
         new_set[0].get_mut(&Confirm2).unwrap().inputs =
-            _vec_vec_to_bigdecimal(vec![vec![10., 14.5, 0.5]]);
+            _vec_vec_to_inputs(vec![vec![10., 14.5, 0.5]]);
         new_set[1].get_mut(&Confirm2).unwrap().inputs =
-            _vec_vec_to_bigdecimal(vec![vec![15., 20., 0.5]]);
+            _vec_vec_to_inputs(vec![vec![15., 20., 0.5]]);
         assert_eq!(set.slice_longest_input(), Some(new_set));
+
+        let slices = set.clone().slice_recursive(100).unwrap();
+        assert!(slices.len() > 1);
+        // the slices cover every combination exactly once
+        let counts = slices
+            .iter()
+            .map(|s| s.count_inputs_crossed().unwrap())
+            .collect::<Vec<_>>();
+        assert!(counts.iter().all(|c| *c < 100));
+        assert_eq!(counts.iter().sum::<u64>(), 11 * 21);
+        assert_eq!(
+            IndicatorSet::merge_ranges(&slices.iter().collect::<Vec<_>>()),
+            Some(set.clone())
+        );
+        assert_eq!(IndicatorSet::merge_ranges(&[]), None);
+
+        let mut set = set;
+        set.get_mut(&Confirm).unwrap().inputs = _vec_vec_to_inputs(vec![vec![20., 10., 1.]]);
+        let err = set.slice_recursive(100).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Confirm indicator ama: input0: the start"));
+    }
+
+    #[test]
+    fn merge_descending_ranges_test() {
+        let mut set = IndicatorSet::_new_test(2);
+        for indi in set.values_mut() {
+            indi.inputs = _vec_vec_to_inputs(vec![vec![20., 10., -0.5]]);
+        }
+
+        let slices = set.clone().slice_recursive(100).unwrap();
+        assert!(slices.len() > 1);
+        assert_eq!(
+            IndicatorSet::merge_ranges(&slices.iter().collect::<Vec<_>>()),
+            Some(set)
+        );
     }
 }
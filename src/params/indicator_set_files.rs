@@ -1,19 +1,220 @@
 use super::indi_func::IndiFunc;
 use super::indicator::Indicator;
 use super::indicator_set::IndicatorSet;
+use anyhow::{Context, Result};
+use glob::glob;
 use std::collections::HashMap;
-use std::path::PathBuf;
-
-impl From<HashMap<IndiFunc, PathBuf>> for IndicatorSet {
-    fn from(s: HashMap<IndiFunc, PathBuf>) -> Self {
-        s.into_iter()
-            .map(|(func, indi_file)| {
-                (
-                    func,
-                    serde_any::from_file::<Indicator, _>(indi_file).unwrap(),
-                )
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// an indicator file that can't be read
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndicatorFileError {
+    pub func: IndiFunc,
+    pub file: PathBuf,
+    // the line of a parse error. The message names the field
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for IndicatorFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.func, self.file)?;
+        if let Some(line) = self.line {
+            write!(f, " line {}", line)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl IndicatorFileError {
+    fn new(func: IndiFunc, file: &Path, err: serde_any::Error) -> Self {
+        let (line, message) = match err {
+            serde_any::Error::Yaml(e) => (e.location().map(|l| l.line()), e.to_string()),
+            serde_any::Error::Json(e) => (Some(e.line()), e.to_string()),
+            e => (None, e.to_string()),
+        };
+        IndicatorFileError {
+            func,
+            file: file.to_path_buf(),
+            line,
+            message,
+        }
+    }
+}
+
+// all indicator files of an indicator set that can't be read
+#[derive(Debug, PartialEq, Clone)]
+pub struct IndicatorSetError(pub Vec<IndicatorFileError>);
+
+impl fmt::Display for IndicatorSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} indicator file(s) can't be read", self.0.len())?;
+        for e in &self.0 {
+            write!(f, "\n  {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for IndicatorSetError {}
+
+impl TryFrom<HashMap<IndiFunc, PathBuf>> for IndicatorSet {
+    type Error = IndicatorSetError;
+
+    fn try_from(s: HashMap<IndiFunc, PathBuf>) -> Result<Self, Self::Error> {
+        let mut set = HashMap::new();
+        let mut errors = Vec::new();
+        for (func, indi_file) in s {
+            match serde_any::from_file::<Indicator, _>(&indi_file) {
+                Ok(indi) => {
+                    set.insert(func, indi);
+                }
+                Err(e) => errors.push(IndicatorFileError::new(func, &indi_file, e)),
+            }
+        }
+        if !errors.is_empty() {
+            errors.sort_by(|a, b| a.file.cmp(&b.file));
+            return Err(IndicatorSetError(errors));
+        }
+        Ok(set.into())
+    }
+}
+
+impl IndicatorSet {
+    // write every indicator to <dir>/<func>.yaml. The returned map can be used as indi_set of a
+    // RunParamsFile
+    pub fn _to_files(&self, dir: &Path) -> Result<HashMap<IndiFunc, PathBuf>> {
+        fs::create_dir_all(dir)?;
+        self.iter()
+            .map(|(func, indi)| {
+                let file = dir.join(format!("{}.yaml", func));
+                serde_any::to_file(&file, indi)
+                    .map_err(|e| anyhow!("writing {:?} failed: {:?}", file, e))?;
+                Ok((*func, file))
             })
-            .collect::<HashMap<IndiFunc, Indicator>>()
-            .into()
+            .collect()
+    }
+}
+
+// all indicator configs (yaml or json) in dir, sorted by file name
+pub fn read_indicator_catalog(dir: &Path) -> Result<Vec<(PathBuf, Indicator)>> {
+    read_indicator_glob(&dir.join("*"))
+}
+
+// all indicator configs (yaml or json) matching the glob pattern, sorted by file name
+pub fn read_indicator_glob(pattern: &Path) -> Result<Vec<(PathBuf, Indicator)>> {
+    let mut files = glob(&pattern.to_string_lossy())?
+        .filter_map(Result::ok)
+        .filter(|f| {
+            matches!(
+                f.extension().and_then(|e| e.to_str()),
+                Some("yaml") | Some("yml") | Some("json")
+            )
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+        .into_iter()
+        .map(|f| {
+            let indi = serde_any::from_file::<Indicator, _>(&f)
+                .map_err(|e| anyhow!("{:?}", e))
+                .context(format!("reading indicator {:?} failed", f))?;
+            Ok((f, indi))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::signal_class::SignalClass;
+
+    #[test]
+    fn to_files_test() {
+        let set: IndicatorSet = [
+            (
+                IndiFunc::Confirm,
+                Indicator {
+                    name: "ama".to_string(),
+                    filename: None,
+                    shift: 0,
+                    inputs: _vec_vec_to_inputs(vec![vec![11.], vec![0.25]]),
+                    buffers: None,
+                    params: None,
+                    class: SignalClass::Preset,
+                },
+            ),
+            (
+                IndiFunc::Exit,
+                Indicator {
+                    name: "rex".to_string(),
+                    filename: Some("Rex".to_string()),
+                    shift: 1,
+                    inputs: _vec_vec_to_inputs(vec![vec![5.]]),
+                    buffers: Some(vec![0, 1]),
+                    params: None,
+                    class: SignalClass::TwoLinesCross,
+                },
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect::<HashMap<_, _>>()
+        .into();
+
+        let dir = Path::new("/tmp/backtestd_to_files_test");
+        let _ = fs::remove_dir_all(dir);
+        let files = set._to_files(dir).unwrap();
+        assert_eq!(files[&IndiFunc::Confirm], dir.join("Confirm.yaml"));
+        assert_eq!(IndicatorSet::try_from(files).unwrap(), set);
+
+        let catalog = read_indicator_catalog(dir).unwrap();
+        assert_eq!(catalog.len(), 2);
+        assert_eq!(catalog[0].0, dir.join("Confirm.yaml"));
+        assert_eq!(catalog[0].1, set[&IndiFunc::Confirm]);
+        assert_eq!(catalog[1].1, set[&IndiFunc::Exit]);
+        assert_eq!(
+            read_indicator_glob(&dir.join("Ex*")).unwrap()[0].1,
+            set[&IndiFunc::Exit]
+        );
+        assert!(
+            read_indicator_catalog(Path::new("/tmp/backtestd_no_catalog"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn try_from_errors_test() {
+        let dir = Path::new("/tmp/backtestd_try_from_test");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("bad.yaml"), "name: ama\nshift: x\n").unwrap();
+
+        let files: HashMap<IndiFunc, PathBuf> = [
+            (IndiFunc::Confirm, dir.join("missing.yaml")),
+            (IndiFunc::Exit, dir.join("bad.yaml")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let err = IndicatorSet::try_from(files).unwrap_err();
+
+        // all files are reported, not only the first one
+        assert_eq!(err.0.len(), 2);
+        assert_eq!(err.0[0].func, IndiFunc::Exit);
+        assert_eq!(err.0[0].file, dir.join("bad.yaml"));
+        assert_eq!(err.0[0].line, Some(2));
+        assert_eq!(err.0[1].func, IndiFunc::Confirm);
+        assert_eq!(err.0[1].line, None);
+
+        let msg = err.to_string();
+        assert!(msg.starts_with("2 indicator file(s) can't be read"));
+        assert!(msg.contains("Exit: \"/tmp/backtestd_try_from_test/bad.yaml\" line 2: "));
+        assert!(msg.contains("missing.yaml"));
     }
 }
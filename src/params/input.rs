@@ -0,0 +1,477 @@
+use anyhow::{Context, Result};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+// the type of the input in the indicator. Int inputs are written without decimals
+#[derive(Debug, PartialEq, PartialOrd, Eq, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputKind {
+    Int,
+    #[default]
+    Double,
+    String,
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InputValue {
+    Number(BigDecimal),
+    Text(String),
+}
+
+impl fmt::Display for InputValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputValue::Number(n) => write!(f, "{}", n),
+            InputValue::Text(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub enum InputRange {
+    Fixed(InputValue),
+    // optimized from start to stop. The default is 0
+    Range {
+        start: BigDecimal,
+        stop: BigDecimal,
+        step: BigDecimal,
+    },
+    RangeWithDefault {
+        default: BigDecimal,
+        start: BigDecimal,
+        stop: BigDecimal,
+        step: BigDecimal,
+    },
+}
+
+// An input of an indicator. In a config it's either a map with the fields of InputRange, a single
+// value, or the old positional form [value], [start, stop, step] or [default, start, stop, step].
+// Inputs without name and kind are written in the positional form.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
+#[serde(try_from = "InputRepr", into = "InputRepr")]
+pub struct Input {
+    pub name: Option<String>,
+    pub kind: InputKind,
+    pub range: InputRange,
+}
+
+impl Input {
+    #[cfg(test)]
+    pub fn fixed(value: BigDecimal) -> Self {
+        InputRange::Fixed(InputValue::Number(value)).into()
+    }
+
+    #[cfg(test)]
+    pub fn range(start: BigDecimal, stop: BigDecimal, step: BigDecimal) -> Self {
+        InputRange::Range { start, stop, step }.into()
+    }
+
+    #[cfg(test)]
+    pub fn is_optimized(&self) -> bool {
+        self.range.bounds().is_some()
+    }
+
+    // The number of values of the input, counted exactly on the grid of the step. The stop is
+    // included if it's on the grid. Ranges that never reach the stop are an error.
+    pub fn count(&self) -> Result<u64> {
+        let (start, stop, step) = match self.range.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(1),
+        };
+        // descending ranges have a negative step
+        if step.is_zero() {
+            bail!("the step is zero");
+        } else if *step > BigDecimal::zero() && start > stop {
+            bail!("the start {} is greater than the stop {}", start, stop);
+        } else if *step < BigDecimal::zero() && start < stop {
+            bail!(
+                "the step {} is negative but the start {} is less than the stop {}",
+                step,
+                start,
+                stop
+            );
+        }
+        // the quotient is never negative so with_scale(0) is the floor
+        ((stop - start) / step)
+            .with_scale(0)
+            .to_u64()
+            .and_then(|steps| steps.checked_add(1))
+            .context(format!(
+                "the range from {} to {} in steps of {} has too many values",
+                start, stop, step
+            ))
+    }
+
+    // Cuts an optimized input into parts slices with about the same number of values on the grid
+    // of the step. The last slices are the longer ones.
+    pub fn chunks(&self, parts: u64) -> Result<Vec<Input>> {
+        let len = self.count()?;
+        ensure!(
+            parts >= 1 && parts <= len,
+            "{} values can't be cut into {} slices",
+            len,
+            parts
+        );
+        let (start, _, step) = match self.range.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(vec![self.clone()]),
+        };
+        let (size, longer) = (len / parts, len % parts);
+        let mut offset = 0;
+        Ok((0..parts)
+            .map(|k| {
+                let n = if k >= parts - longer { size + 1 } else { size };
+                let mut chunk = self.clone();
+                let (chunk_start, chunk_stop, _) = chunk.range.bounds_mut().unwrap();
+                *chunk_start = start + step * BigDecimal::from(offset);
+                *chunk_stop = start + step * BigDecimal::from(offset + n - 1);
+                offset += n;
+                chunk
+            })
+            .collect())
+    }
+
+    // value||start||step||stop||optimize as in the .set file
+    pub fn to_param_str(&self) -> String {
+        let num = |v: &BigDecimal| match self.kind {
+            InputKind::Int => format!("{}", v.with_scale(0)),
+            _ => format!("{:.5}", v),
+        };
+        match &self.range {
+            InputRange::Fixed(InputValue::Number(v)) => format!("{}||0||0||0||N", num(v)),
+            InputRange::Fixed(InputValue::Text(t)) => format!("{}||0||0||0||N", t),
+            InputRange::Range { start, stop, step } => {
+                format!("0||{}||{}||{}||Y", num(start), num(step), num(stop))
+            }
+            InputRange::RangeWithDefault {
+                default,
+                start,
+                stop,
+                step,
+            } => format!(
+                "{}||{}||{}||{}||Y",
+                num(default),
+                num(start),
+                num(step),
+                num(stop)
+            ),
+        }
+    }
+}
+
+impl From<InputRange> for Input {
+    fn from(range: InputRange) -> Self {
+        Input {
+            name: None,
+            kind: InputKind::default(),
+            range,
+        }
+    }
+}
+
+impl InputRange {
+    // start, stop and step of an optimized input
+    pub fn bounds(&self) -> Option<(&BigDecimal, &BigDecimal, &BigDecimal)> {
+        match self {
+            InputRange::Fixed(_) => None,
+            InputRange::Range { start, stop, step }
+            | InputRange::RangeWithDefault {
+                start, stop, step, ..
+            } => Some((start, stop, step)),
+        }
+    }
+
+    pub fn bounds_mut(&mut self) -> Option<(&mut BigDecimal, &mut BigDecimal, &mut BigDecimal)> {
+        match self {
+            InputRange::Fixed(_) => None,
+            InputRange::Range { start, stop, step }
+            | InputRange::RangeWithDefault {
+                start, stop, step, ..
+            } => Some((start, stop, step)),
+        }
+    }
+}
+
+// the old positional form
+impl TryFrom<Vec<BigDecimal>> for Input {
+    type Error = anyhow::Error;
+
+    fn try_from(v: Vec<BigDecimal>) -> Result<Self> {
+        let len = v.len();
+        let mut v = v.into_iter();
+        let mut next = || v.next().unwrap();
+        Ok(match len {
+            1 => InputRange::Fixed(InputValue::Number(next())),
+            3 => InputRange::Range {
+                start: next(),
+                stop: next(),
+                step: next(),
+            },
+            4 => InputRange::RangeWithDefault {
+                default: next(),
+                start: next(),
+                stop: next(),
+                step: next(),
+            },
+            n => bail!(
+                "expected [value], [start, stop, step] or [default, start, stop, step] \
+                 but got {} values",
+                n
+            ),
+        }
+        .into())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum InputRepr {
+    Positional(Vec<BigDecimal>),
+    Value(InputValue),
+    Named(NamedInput),
+}
+
+#[derive(Serialize, Deserialize)]
+struct NamedInput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default)]
+    kind: InputKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<InputValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<BigDecimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start: Option<BigDecimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stop: Option<BigDecimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    step: Option<BigDecimal>,
+}
+
+impl TryFrom<InputRepr> for Input {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: InputRepr) -> Result<Self> {
+        let n = match repr {
+            InputRepr::Positional(v) => return Input::try_from(v),
+            InputRepr::Value(v) => return Ok(InputRange::Fixed(v).into()),
+            InputRepr::Named(n) => n,
+        };
+        let range = match (n.value, n.default, n.start, n.stop, n.step) {
+            (Some(value), None, None, None, None) => InputRange::Fixed(value),
+            (None, None, Some(start), Some(stop), Some(step)) => {
+                InputRange::Range { start, stop, step }
+            }
+            (None, Some(default), Some(start), Some(stop), Some(step)) => {
+                InputRange::RangeWithDefault {
+                    default,
+                    start,
+                    stop,
+                    step,
+                }
+            }
+            _ => bail!(
+                "input {} needs either a value or start, stop and step with an optional default",
+                n.name.unwrap_or_default()
+            ),
+        };
+        Ok(Input {
+            name: n.name,
+            kind: n.kind,
+            range,
+        })
+    }
+}
+
+impl From<Input> for InputRepr {
+    fn from(input: Input) -> Self {
+        if input.name.is_none() && input.kind == InputKind::default() {
+            match input.range {
+                InputRange::Fixed(InputValue::Number(v)) => return InputRepr::Positional(vec![v]),
+                InputRange::Range { start, stop, step } => {
+                    return InputRepr::Positional(vec![start, stop, step])
+                }
+                InputRange::RangeWithDefault {
+                    default,
+                    start,
+                    stop,
+                    step,
+                } => return InputRepr::Positional(vec![default, start, stop, step]),
+                InputRange::Fixed(InputValue::Text(_)) => {}
+            }
+        }
+        let mut n = NamedInput {
+            name: input.name,
+            kind: input.kind,
+            value: None,
+            default: None,
+            start: None,
+            stop: None,
+            step: None,
+        };
+        match input.range {
+            InputRange::Fixed(v) => n.value = Some(v),
+            InputRange::Range { start, stop, step } => {
+                n.start = Some(start);
+                n.stop = Some(stop);
+                n.step = Some(step);
+            }
+            InputRange::RangeWithDefault {
+                default,
+                start,
+                stop,
+                step,
+            } => {
+                n.default = Some(default);
+                n.start = Some(start);
+                n.stop = Some(stop);
+                n.step = Some(step);
+            }
+        }
+        InputRepr::Named(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::{_vec_to_input, _vec_vec_to_inputs};
+
+    #[test]
+    fn deserialize_test() {
+        let inputs: Vec<Input> = serde_json::from_str(
+            r#"[
+                [14.0],
+                [10, 20, 1],
+                [15, 10, 20, "0.5"],
+                7,
+                "close",
+                {"name": "period", "kind": "int", "start": 10, "stop": 20, "step": 2},
+                {"name": "mode", "kind": "string", "value": "ema"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(inputs[0], _vec_to_input(vec![14.]));
+        assert_eq!(
+            inputs[1].range,
+            InputRange::Range {
+                start: 10.into(),
+                stop: 20.into(),
+                step: 1.into()
+            }
+        );
+        assert_eq!(
+            inputs[2].range,
+            InputRange::RangeWithDefault {
+                default: 15.into(),
+                start: 10.into(),
+                stop: 20.into(),
+                step: "0.5".parse().unwrap()
+            }
+        );
+        assert_eq!(inputs[3], Input::fixed(7.into()));
+        assert_eq!(
+            inputs[4].range,
+            InputRange::Fixed(InputValue::Text("close".to_string()))
+        );
+        assert_eq!(inputs[5].name, Some("period".to_string()));
+        assert_eq!(inputs[5].kind, InputKind::Int);
+        assert_eq!(inputs[5].count().unwrap(), 6);
+        assert_eq!(inputs[6].kind, InputKind::String);
+
+        // the positional form only has 1, 3 or 4 values
+        assert!(serde_json::from_str::<Input>("[1, 2]").is_err());
+        assert!(serde_json::from_str::<Input>(r#"{"start": 1, "stop": 2}"#).is_err());
+        assert!(serde_json::from_str::<Input>(r#"{"value": 1, "step": 2}"#).is_err());
+
+        // round trip
+        let json = serde_json::to_string(&inputs).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Input>>(&json).unwrap(), inputs);
+        assert_eq!(
+            serde_json::to_value(&inputs[1]).unwrap(),
+            serde_json::to_value(vec![BigDecimal::from(10), 20.into(), 1.into()]).unwrap()
+        );
+    }
+
+    #[test]
+    fn count_test() {
+        assert_eq!(_vec_to_input(vec![3.]).count().unwrap(), 1);
+        assert_eq!(_vec_to_input(vec![10., 20., 1.]).count().unwrap(), 11);
+        assert_eq!(_vec_to_input(vec![10., 20., 3.]).count().unwrap(), 4);
+        assert_eq!(_vec_to_input(vec![15., 10., 20., 0.5]).count().unwrap(), 21);
+        assert_eq!(_vec_to_input(vec![20., 10., -0.5]).count().unwrap(), 21);
+        assert_eq!(_vec_to_input(vec![5., 5., 1.]).count().unwrap(), 1);
+
+        // 0.1 can't be represented as f32
+        let input = Input::range(
+            "0.1".parse().unwrap(),
+            "1.0".parse().unwrap(),
+            "0.1".parse().unwrap(),
+        );
+        assert_eq!(input.count().unwrap(), 10);
+        let input = Input::range(0.into(), 1.into(), "0.3".parse().unwrap());
+        assert_eq!(input.count().unwrap(), 4);
+
+        assert!(_vec_to_input(vec![10., 20., 0.]).count().is_err());
+        assert!(_vec_to_input(vec![20., 10., 1.]).count().is_err());
+        assert!(_vec_to_input(vec![10., 20., -1.]).count().is_err());
+        let input = Input::range(
+            0.into(),
+            "1000000000000000000000000000000".parse().unwrap(),
+            1.into(),
+        );
+        assert!(input.count().is_err());
+    }
+
+    #[test]
+    fn chunks_test() {
+        let input = _vec_to_input(vec![10., 20., 1.]);
+        assert_eq!(
+            input.chunks(3).unwrap(),
+            _vec_vec_to_inputs(vec![
+                vec![10., 12., 1.],
+                vec![13., 16., 1.],
+                vec![17., 20., 1.]
+            ])
+        );
+        assert_eq!(input.chunks(1).unwrap(), vec![input.clone()]);
+        assert_eq!(input.chunks(11).unwrap().len(), 11);
+        assert!(input.chunks(0).is_err());
+        assert!(input.chunks(12).is_err());
+
+        let input = _vec_to_input(vec![15., 20., 10., -0.5]);
+        assert_eq!(
+            input.chunks(2).unwrap(),
+            _vec_vec_to_inputs(vec![vec![15., 20., 15.5, -0.5], vec![15., 15., 10., -0.5]])
+        );
+
+        let input = _vec_to_input(vec![3.]);
+        assert_eq!(input.chunks(1).unwrap(), vec![input]);
+    }
+
+    #[test]
+    fn to_param_str_test() {
+        assert_eq!(
+            _vec_to_input(vec![3.]).to_param_str(),
+            "3.00000||0||0||0||N"
+        );
+        assert_eq!(
+            _vec_to_input(vec![10., 200., 0.5]).to_param_str(),
+            "0||10.00000||0.50000||200.00000||Y"
+        );
+        assert_eq!(
+            _vec_to_input(vec![15., 10., 20., 0.5]).to_param_str(),
+            "15.00000||10.00000||0.50000||20.00000||Y"
+        );
+
+        let mut input = _vec_to_input(vec![10., 20., 2.]);
+        input.kind = InputKind::Int;
+        assert_eq!(input.to_param_str(), "0||10||2||20||Y");
+        input.range = InputRange::Fixed(InputValue::Text("ema".to_string()));
+        assert_eq!(input.to_param_str(), "ema||0||0||0||N");
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::{ensure, Context, Result};
 use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 pub mod common_params;
@@ -9,18 +10,31 @@ pub mod indi_func;
 pub mod indicator;
 pub mod indicator_set;
 pub mod indicator_set_files;
+pub mod input;
+pub mod pipeline_params;
 pub mod run_params;
 pub mod run_params_file;
+pub mod set_file;
 pub mod signal_class;
+pub mod slicing;
+pub mod split_params;
+pub mod terminal_ini;
+pub mod timeout_params;
 pub mod to_param_string;
+pub mod validation;
+pub mod walk_forward_params;
 
 pub use common_params::CommonParams;
 pub use indi_func::IndiFunc;
 pub use indicator_set::IndicatorSet;
+pub use input::{Input, InputRange, InputValue};
+pub use pipeline_params::PipelineParams;
 pub use run_params::RunParams;
 pub use run_params_file::RunParamsFile;
-pub use signal_class::SignalClass;
+pub use split_params::SplitParams;
+pub use timeout_params::TimeoutParams;
 pub use to_param_string::ToParamString;
+pub use walk_forward_params::{WalkForwardParams, WalkForwardWindow};
 
 // const FOREX_PAIRS: &'static [&'static str] = &[
 //     "EURUSD", "GBPUSD", "USDCHF", "USDJPY", "USDCAD", "AUDUSD", "EURCHF", "EURJPY", "EURGBP",
@@ -40,7 +54,7 @@ pub fn to_terminal_config(common: &CommonParams, run: &RunParams) -> Result<Stri
         .join(
             run.name.clone()
                 + "_"
-                + &run
+                + run
                     .symbols
                     .iter()
                     .max_by(|x, y| x.cmp(y))
@@ -85,7 +99,7 @@ pub fn get_reports_dir(common: &CommonParams) -> Result<PathBuf> {
 }
 
 pub fn get_reports_full_path(common: &CommonParams, run: &RunParams) -> Result<PathBuf> {
-    Ok(get_reports_dir(&common)?.join(run.get_reports_filename().with_extension("xml")))
+    Ok(get_reports_dir(common)?.join(run.get_reports_filename().with_extension("xml")))
 }
 
 #[derive(Debug, PartialEq, Copy, Clone, Serialize_repr, Deserialize_repr)]
@@ -100,6 +114,7 @@ pub enum BacktestModel {
 
 // fn f(m: &)
 
+#[allow(clippy::derivable_impls)]
 impl Default for BacktestModel {
     fn default() -> Self {
         BacktestModel::OpenPrice
@@ -115,6 +130,7 @@ pub enum OptimizeMode {
     AllSymbols = 3, // "All symbols selected in Market Watch"
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for OptimizeMode {
     fn default() -> Self {
         OptimizeMode::Complete
@@ -133,6 +149,7 @@ pub enum OptimizeCrit {
     Custom = 6, // a custom optimization criterion received from the OnTester() function in the Expert Advisor).
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for OptimizeCrit {
     fn default() -> Self {
         OptimizeCrit::Custom
@@ -148,6 +165,7 @@ pub enum StoreResults {
               // Results = 3,     // "All symbols selected in Market Watch"
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for StoreResults {
     fn default() -> Self {
         StoreResults::None
@@ -162,6 +180,14 @@ pub fn _vec_vec_to_bigdecimal(vec: Vec<Vec<f32>>) -> Vec<Vec<BigDecimal>> {
     vec.iter().map(|v| _vec_to_bigdecimal(v.to_vec())).collect()
 }
 
+pub fn _vec_to_input(vec: Vec<f32>) -> Input {
+    Input::try_from(_vec_to_bigdecimal(vec)).unwrap()
+}
+
+pub fn _vec_vec_to_inputs(vec: Vec<Vec<f32>>) -> Vec<Input> {
+    vec.into_iter().map(_vec_to_input).collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::indi_func::IndiFunc;
@@ -172,6 +198,7 @@ mod test {
     use super::*;
     use chrono::DateTime;
     use std::collections::HashMap;
+    use std::convert::TryFrom;
     use std::path::Path;
     use std::str::FromStr;
 
@@ -234,6 +261,7 @@ mod test {
         let mut common = CommonParams {
             params_file: "expert_params.set".to_string(),
             wine: false,
+            wineprefix: None,
             terminal_exe: PathBuf::from(r"C:\terminal64.exe"),
             workdir: PathBuf::from(r"C:/workdir"),
             reports: PathBuf::from("reports"),
@@ -248,6 +276,10 @@ mod test {
             currency: "USD".to_string(),
             leverage: 100,
             execution_mode: 0,
+            terminals: Vec::new(),
+            split: Default::default(),
+            timeouts: Default::default(),
+            journal: PathBuf::from("backtestd-queue.jsonl"),
         };
 
         let run = RunParams {
@@ -267,6 +299,8 @@ mod test {
             visual: false,
             symbols: vec!["USDCHF".to_string()],
             store_results: StoreResults::None,
+            slice: None,
+            walk_forward: None,
         };
 
         assert_eq!(
@@ -308,6 +342,7 @@ mod test {
         let term_params = CommonParams {
             params_file: "expert_params.set".to_string(),
             wine: false,
+            wineprefix: None,
             terminal_exe: PathBuf::from(r"C:\terminal64.exe"),
             workdir: workdir.to_path_buf(),
             reports: PathBuf::from("reports"),
@@ -322,6 +357,10 @@ mod test {
             currency: "USD".to_string(),
             leverage: 100,
             execution_mode: 0,
+            terminals: Vec::new(),
+            split: Default::default(),
+            timeouts: Default::default(),
+            journal: PathBuf::from("backtestd-queue.jsonl"),
         };
 
         let j = r#"{"params_file":"expert_params.set",
@@ -352,7 +391,7 @@ mod test {
                         name: "ma".to_string(),
                         filename: None,
                         shift: 0,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![1.], vec![1., 100., 3.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![1.], vec![1., 100., 3.]]),
                         buffers: None,
                         params: None,
                         class: Preset,
@@ -363,7 +402,7 @@ mod test {
                     Indicator {
                         name: "ma2".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![1.], vec![10., 200., 5.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![1.], vec![10., 200., 5.]]),
                         shift: 1,
                         buffers: None,
                         params: None,
@@ -375,10 +414,7 @@ mod test {
                     Indicator {
                         name: "exitor".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![
-                            vec![14., 100., 3.],
-                            vec![1., 30., 2.],
-                        ]),
+                        inputs: _vec_vec_to_inputs(vec![vec![14., 100., 3.], vec![1., 30., 2.]]),
                         shift: 2,
                         buffers: None,
                         params: None,
@@ -390,7 +426,7 @@ mod test {
                     Indicator {
                         name: "Ichy".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![41.], vec![10.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![41.], vec![10.]]),
                         shift: 0,
                         buffers: None,
                         params: None,
@@ -402,7 +438,7 @@ mod test {
                     Indicator {
                         name: "WAE".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![7.], vec![222.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![7.], vec![222.]]),
                         shift: 0,
                         buffers: None,
                         params: None,
@@ -428,6 +464,8 @@ mod test {
             visual: false,
             symbols: vec!["EURUSD".to_string(), "AUDCAD".into()],
             store_results: StoreResults::SideChanges,
+            slice: None,
+            walk_forward: None,
         };
 
         let run_string = r#"{
@@ -506,12 +544,15 @@ mod test {
         .cloned()
         .collect();
 
-        assert_eq!(IndicatorSet::from(indi_set.clone()), run.indi_set);
+        assert_eq!(
+            IndicatorSet::try_from(indi_set.clone()).unwrap(),
+            run.indi_set
+        );
 
         let run_cl = run.clone();
         let rpf = RunParamsFile {
             name: run_cl.name,
-            indi_set: indi_set.into(),
+            indi_set,
             date: run_cl.date,
             backtest_model: run_cl.backtest_model,
             optimize: run_cl.optimize,
@@ -519,11 +560,12 @@ mod test {
             visual: run_cl.visual,
             symbols: run_cl.symbols,
             store_results: run_cl.store_results,
+            walk_forward: run_cl.walk_forward,
         };
 
         let _ = serde_any::to_file("/tmp/run.yaml", &rpf);
 
-        assert_eq!(RunParams::from(rpf), run);
+        assert_eq!(RunParams::try_from(rpf).unwrap(), run);
     }
 
     /* #[test]
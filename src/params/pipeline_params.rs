@@ -0,0 +1,33 @@
+use super::indi_func::IndiFunc;
+use crate::results::best::RankParams;
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// A divide-and-conquer pipeline: every stage tests the candidates of a catalog in one IndiFunc on
+// top of the winners of the previous stage.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PipelineParams {
+    pub name: String,
+    // RunParamsFile with the date, symbols and test settings. Its indi_set holds the indicators
+    // that are fixed for all stages and may be empty
+    pub run: PathBuf,
+    pub stages: Vec<PipelineStage>,
+    // how a candidate is scored by its best pass. top is ignored
+    #[serde(default)]
+    pub rank: RankParams,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PipelineStage {
+    pub func: IndiFunc,
+    // directory with the indicator configs of the candidates
+    pub catalog: PathBuf,
+    // number of winners carried on to the next stage
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+}
+
+fn default_keep() -> usize {
+    3
+}
@@ -16,13 +16,19 @@ pub struct RunParams {
     pub visual: bool,
     pub symbols: Vec<String>,
     pub store_results: StoreResults,
+    // index of the slice if the run was sliced by split_run_into_queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slice: Option<usize>,
+    // run a walk-forward analysis instead of a single optimization
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub walk_forward: Option<WalkForwardParams>,
 }
 
 impl ToParamString for RunParams {
     fn to_param_string(&self) -> String {
         let string = self.to_param_string_vec().join("\n");
         debug!("Params config for terminal:\n{}", string);
-        return string;
+        string
     }
 }
 
@@ -69,21 +75,22 @@ OptimizationCriterion={opti_crit}",
         )
     }
 
-    pub fn split_run_into_queue(self) -> Vec<Self> {
+    pub fn split_run_into_queue(self, split: &SplitParams) -> Result<Vec<Self>> {
         let run = self;
         let optimize = run.optimize;
         let mut runs = match optimize {
-            OptimizeMode::Complete => run.split_too_many_runs(),
+            OptimizeMode::Complete if split.slices() => run.split_too_many_runs(split)?,
             _ => vec![run],
         };
 
-        if optimize != OptimizeMode::Genetic {
+        if optimize != OptimizeMode::Genetic && split.symbols() {
             // create a vec of new runs with only a single Symbol
             // if we test in Genetic mode use all Symbols
-            runs = runs
-                .into_iter()
-                .flat_map(|r| r.split_per_symbol())
-                .collect();
+            let mut split_runs = Vec::with_capacity(runs.len());
+            for r in runs {
+                split_runs.extend(r.split_per_symbol(split.run_limit_multi_currency)?);
+            }
+            runs = split_runs;
         }
 
         info!(
@@ -96,35 +103,34 @@ OptimizationCriterion={opti_crit}",
                 .join("\n")
         );
 
-        runs
+        Ok(runs)
     }
 
-    fn split_too_many_runs(self) -> Vec<Self> {
-        let runs: Vec<RunParams>;
+    fn split_too_many_runs(self, split: &SplitParams) -> Result<Vec<Self>> {
         let run = self;
-        // MT5 forces genetic optimization if there are more than 100M possibilities
-        let new_sets = run.clone().indi_set.slice_recursive(100_000_000); // TODO implement slice_recursive on &self to not move indi_set out of run
+        let new_sets = split.slice(run.clone().indi_set)?; // TODO slice on &self to not move indi_set out of run
 
-        if new_sets.len() > 1 {
-            runs = new_sets
+        let runs = if new_sets.len() > 1 {
+            new_sets
                 .into_iter()
                 .enumerate()
                 .map(|(i, s)| {
                     let mut r = run.clone();
                     r.indi_set = s;
                     r.name = format!("{}_{}", r.name, i);
+                    r.slice = Some(i);
                     r
                 })
-                .collect::<Vec<RunParams>>();
+                .collect::<Vec<RunParams>>()
         } else {
-            runs = vec![run];
-        }
-        runs
+            vec![run]
+        };
+        Ok(runs)
     }
 
-    fn split_per_symbol(self) -> Vec<Self> {
+    fn split_per_symbol(self, run_limit_multi_currency: u64) -> Result<Vec<Self>> {
         let r = self;
-        if r.indi_set.count_inputs_crossed() > crate::RUN_LIMIT_MULTI_CURRENCY {
+        let runs = if r.indi_set.count_inputs_crossed()? > run_limit_multi_currency {
             r.symbols
                 .iter()
                 .map(|s| {
@@ -138,7 +144,8 @@ OptimizationCriterion={opti_crit}",
                 .collect::<Vec<RunParams>>()
         } else {
             vec![r]
-        }
+        };
+        Ok(runs)
     }
 
     pub fn _new_test(num: usize) -> Self {
@@ -156,12 +163,11 @@ OptimizationCriterion={opti_crit}",
             optimize: OptimizeMode::Complete,
             optimize_crit: OptimizeCrit::Custom,
             visual: false,
-            symbols: vec!["USDCHF", "NZDAUD"]
-                .iter()
-                .map(|s| s.to_string())
-                .collect(),
+            symbols: ["USDCHF", "NZDAUD"].iter().map(|s| s.to_string()).collect(),
             store_results: StoreResults::None,
             indi_set: IndicatorSet::_new_test(num),
+            slice: None,
+            walk_forward: None,
         }
     }
 }
@@ -172,8 +178,9 @@ mod test {
     use super::indi_func::IndiFunc::*;
     use super::indicator::Indicator;
     use super::signal_class::SignalClass::*;
+    use super::split_params::{SliceStrategy, SplitStrategy};
     use super::*;
-    use crate::params::_vec_vec_to_bigdecimal;
+    use crate::params::_vec_vec_to_inputs;
     use std::collections::HashMap;
 
     #[test]
@@ -181,6 +188,7 @@ mod test {
         let common = CommonParams {
             params_file: "expert_params.set".to_string(),
             wine: false,
+            wineprefix: None,
             terminal_exe: PathBuf::from(r"C:\terminal64.exe"),
             workdir: PathBuf::from(r"C:\workdir"),
             reports: PathBuf::from("reports"),
@@ -195,6 +203,10 @@ mod test {
             currency: "USD".to_string(),
             leverage: 100,
             execution_mode: 0,
+            terminals: Vec::new(),
+            split: Default::default(),
+            timeouts: Default::default(),
+            journal: PathBuf::from("backtestd-queue.jsonl"),
         };
 
         let run = RunParams {
@@ -206,7 +218,7 @@ mod test {
                         name: "ma".to_string(),
                         filename: None,
                         shift: 0,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![1.], vec![1., 100., 3.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![1.], vec![1., 100., 3.]]),
                         buffers: None,
                         params: None,
                         class: Preset,
@@ -217,7 +229,7 @@ mod test {
                     Indicator {
                         name: "ma2".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![1.], vec![10., 200., 5.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![1.], vec![10., 200., 5.]]),
                         shift: 1,
                         buffers: None,
                         params: None,
@@ -229,10 +241,7 @@ mod test {
                     Indicator {
                         name: "exitor".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![
-                            vec![14., 100., 3.],
-                            vec![1., 30., 2.],
-                        ]),
+                        inputs: _vec_vec_to_inputs(vec![vec![14., 100., 3.], vec![1., 30., 2.]]),
                         shift: 2,
                         buffers: None,
                         params: None,
@@ -244,7 +253,7 @@ mod test {
                     Indicator {
                         name: "Ichy".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![41.], vec![10.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![41.], vec![10.]]),
                         shift: 0,
                         buffers: None,
                         params: None,
@@ -256,7 +265,7 @@ mod test {
                     Indicator {
                         name: "WAE".to_string(),
                         filename: None,
-                        inputs: _vec_vec_to_bigdecimal(vec![vec![7.], vec![222.]]),
+                        inputs: _vec_vec_to_inputs(vec![vec![7.], vec![222.]]),
                         shift: 0,
                         buffers: None,
                         params: None,
@@ -280,11 +289,13 @@ mod test {
             optimize: OptimizeMode::Complete,
             optimize_crit: OptimizeCrit::Custom,
             visual: false,
-            symbols: vec!["USDCHF", "AUDCAD", "USDJPY", "USDCAD"]
+            symbols: ["USDCHF", "AUDCAD", "USDJPY", "USDCAD"]
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
             store_results: StoreResults::None,
+            slice: None,
+            walk_forward: None,
         };
 
         assert_eq!(
@@ -319,4 +330,60 @@ Symbol=USDJPY
 Report=reports\test_USDJPY.xml"
         );
     }
+
+    #[test]
+    fn split_run_into_queue_test() {
+        let mut run = RunParams::_new_test(1);
+        run.indi_set.get_mut(&Confirm).unwrap().inputs =
+            _vec_vec_to_inputs(vec![vec![10., 20., 1.]]);
+
+        let mut split = SplitParams {
+            strategy: SplitStrategy::None,
+            max_combinations: 5,
+            run_limit_multi_currency: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            run.clone().split_run_into_queue(&split).unwrap(),
+            vec![run.clone()]
+        );
+
+        split.strategy = SplitStrategy::Symbols;
+        let runs = run.clone().split_run_into_queue(&split).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].symbols, vec!["USDCHF"]);
+        assert_eq!(runs[1].symbols, vec!["NZDAUD"]);
+        assert!(runs.iter().all(|r| r.name == "test" && r.slice.is_none()));
+
+        split.strategy = SplitStrategy::Slices;
+        let runs = run.clone().split_run_into_queue(&split).unwrap();
+        assert!(runs.len() > 1);
+        for (i, r) in runs.iter().enumerate() {
+            assert_eq!(r.slice, Some(i));
+            assert_eq!(r.name, format!("test_{}", i));
+            assert_eq!(r.symbols, run.symbols);
+        }
+
+        split.strategy = SplitStrategy::SlicesAndSymbols;
+        split.run_limit_multi_currency = 1;
+        assert_eq!(
+            run.clone().split_run_into_queue(&split).unwrap().len(),
+            2 * runs.len()
+        );
+
+        // slices small enough to keep all Symbols in one run
+        split.slicing = SliceStrategy::MultiCurrency;
+        split.max_combinations = 100;
+        split.run_limit_multi_currency = 5;
+        let runs = run.clone().split_run_into_queue(&split).unwrap();
+        assert_eq!(runs.len(), 3);
+        assert!(runs.iter().all(|r| r.symbols == run.symbols));
+        assert!(runs
+            .iter()
+            .all(|r| r.indi_set.count_inputs_crossed().unwrap() <= 5));
+
+        // genetic optimization is never split
+        run.optimize = OptimizeMode::Genetic;
+        assert_eq!(run.clone().split_run_into_queue(&split).unwrap(), vec![run]);
+    }
 }
@@ -2,11 +2,14 @@ use chrono::prelude::*;
 use chrono::DateTime;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 
 use super::indi_func::IndiFunc;
+use super::indicator_set_files::{read_indicator_glob, IndicatorSetError};
 use super::run_params::RunParams;
 use super::*;
+use anyhow::Result;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct RunParamsFile {
@@ -19,13 +22,18 @@ pub struct RunParamsFile {
     pub visual: bool,
     pub symbols: Vec<String>,
     pub store_results: StoreResults,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub walk_forward: Option<WalkForwardParams>,
 }
 
-impl From<RunParamsFile> for RunParams {
-    fn from(s: RunParamsFile) -> Self {
-        RunParams {
+// reading the indicator files fails with all files that can't be read
+impl TryFrom<RunParamsFile> for RunParams {
+    type Error = IndicatorSetError;
+
+    fn try_from(s: RunParamsFile) -> Result<Self, Self::Error> {
+        Ok(RunParams {
             name: s.name,
-            indi_set: s.indi_set.into(),
+            indi_set: IndicatorSet::try_from(s.indi_set)?,
             date: s.date,
             backtest_model: s.backtest_model,
             optimize: s.optimize,
@@ -33,6 +41,117 @@ impl From<RunParamsFile> for RunParams {
             visual: s.visual,
             symbols: s.symbols,
             store_results: s.store_results,
+            slice: None,
+            walk_forward: s.walk_forward,
+        })
+    }
+}
+
+// a directory or a glob pattern in indi_set sweeps all indicator configs it contains
+fn is_sweep(path: &Path) -> bool {
+    path.is_dir() || path.to_string_lossy().contains(['*', '?', '['])
+}
+
+impl RunParamsFile {
+    // the IndiFunc whose indicators are swept. Only one IndiFunc can be swept
+    pub fn sweep_func(&self) -> Result<Option<IndiFunc>> {
+        let mut funcs = self
+            .indi_set
+            .iter()
+            .filter(|(_, path)| is_sweep(path))
+            .map(|(func, _)| *func);
+        let func = funcs.next();
+        ensure!(
+            funcs.next().is_none(),
+            "only one IndiFunc of {} can be a directory or a glob",
+            self.name
+        );
+        Ok(func)
+    }
+
+    // One run per indicator config of the swept IndiFunc named <name>_<func>_<file stem>, or the
+    // run itself if nothing is swept
+    pub fn expand(mut self) -> Result<Vec<RunParams>> {
+        let func = match self.sweep_func()? {
+            Some(func) => func,
+            None => return Ok(vec![RunParams::try_from(self)?]),
+        };
+        let path = self.indi_set.remove(&func).unwrap();
+        let pattern = if path.is_dir() {
+            path.join("*")
+        } else {
+            path.clone()
+        };
+        let indis = read_indicator_glob(&pattern)?;
+        ensure!(!indis.is_empty(), "no indicator config matches {:?}", path);
+
+        let run = RunParams::try_from(self)?;
+        Ok(indis
+            .into_iter()
+            .map(|(file, indi)| {
+                let mut r = run.clone();
+                r.name = format!(
+                    "{}_{}_{}",
+                    run.name,
+                    func.to_string().to_lowercase(),
+                    file.file_stem().unwrap_or_default().to_string_lossy()
+                );
+                r.indi_set.insert(func, indi);
+                r
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::indi_func::IndiFunc::*;
+
+    #[test]
+    fn expand_test() {
+        let run = RunParams::_new_test(3);
+        let dir = PathBuf::from("/tmp/backtestd_sweep_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("confirm")).unwrap();
+        let mut indi_set = HashMap::new();
+        for (func, indi) in run.indi_set.iter() {
+            let file = dir.join(format!("{:?}.yaml", func));
+            serde_any::to_file(&file, indi).unwrap();
+            indi_set.insert(*func, file);
         }
+        std::fs::copy(&indi_set[&Confirm], dir.join("confirm/a.yaml")).unwrap();
+        std::fs::copy(&indi_set[&Confirm2], dir.join("confirm/b.yaml")).unwrap();
+
+        let rpf = RunParamsFile {
+            name: run.name.clone(),
+            indi_set,
+            date: run.date,
+            backtest_model: run.backtest_model,
+            optimize: run.optimize,
+            optimize_crit: run.optimize_crit,
+            visual: run.visual,
+            symbols: run.symbols.clone(),
+            store_results: run.store_results,
+            walk_forward: None,
+        };
+        assert_eq!(rpf.sweep_func().unwrap(), None);
+        assert_eq!(rpf.clone().expand().unwrap(), vec![run.clone()]);
+
+        // sweep all confirm indicators in a directory against the fixed rest of the set
+        let mut sweep = rpf;
+        sweep.indi_set.insert(Confirm, dir.join("confirm"));
+        assert_eq!(sweep.sweep_func().unwrap(), Some(Confirm));
+        let runs = sweep.clone().expand().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].name, format!("{}_confirm_a", run.name));
+        assert_eq!(runs[0].indi_set, run.indi_set);
+        assert_eq!(runs[1].indi_set[&Confirm], run.indi_set[&Confirm2]);
+        assert_eq!(runs[1].indi_set[&Confirm3], run.indi_set[&Confirm3]);
+
+        sweep.indi_set.insert(Confirm, dir.join("confirm/b*"));
+        assert_eq!(sweep.clone().expand().unwrap().len(), 1);
+        sweep.indi_set.insert(Confirm3, dir.join("confirm/*.yaml"));
+        assert!(sweep.sweep_func().is_err());
     }
 }
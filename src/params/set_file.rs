@@ -0,0 +1,384 @@
+use super::indi_func::IndiFunc;
+use super::indicator::Indicator;
+use super::indicator_set::IndicatorSet;
+use super::input::{Input, InputKind, InputRange, InputValue};
+use super::signal_class::SignalClass;
+use super::StoreResults;
+
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+// The content of a .set file as written by RunParams::to_param_string. Lines of other experts and
+// comments are ignored.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SetFile {
+    pub name: Option<String>,
+    pub indi_set: IndicatorSet,
+    pub symbols: Vec<String>,
+    pub store_results: Option<StoreResults>,
+}
+
+// the lines of one IndiFunc
+#[derive(Debug, Default)]
+struct IndicatorLines {
+    indicator: Option<String>,
+    class: Option<SignalClass>,
+    shift: u8,
+    inputs: BTreeMap<usize, Input>,
+    buffers: BTreeMap<usize, u8>,
+    params: BTreeMap<usize, BigDecimal>,
+}
+
+impl IndicatorLines {
+    fn set(&mut self, field: &str, value: &str) -> Result<()> {
+        if let Some(i) = indexed(field, "input") {
+            self.inputs.insert(i, parse_input(value)?);
+        } else if let Some(i) = indexed(field, "buffer") {
+            self.buffers.insert(i, value.parse()?);
+        } else if let Some(i) = indexed(field, "param") {
+            self.params.insert(i, number(value)?);
+        } else {
+            match field {
+                "Indicator" => self.indicator = Some(value.to_string()),
+                "SignalClass" => self.class = Some(SignalClass::try_from(value.parse::<u8>()?)?),
+                "Shift" => self.shift = value.parse()?,
+                _ => debug!("ignoring the unknown indicator field {}", field),
+            }
+        }
+        Ok(())
+    }
+
+    // None if no indicator is configured for the func
+    fn into_indicator(self, func: IndiFunc) -> Result<Option<Indicator>> {
+        let name = match self.indicator {
+            Some(name) if !name.is_empty() => name,
+            _ => return Ok(None),
+        };
+        let class = self
+            .class
+            .context(format!("{}_SignalClass is missing", func))?;
+        Ok(Some(Indicator {
+            // only the filename of indicators other than Preset is written
+            filename: match class {
+                SignalClass::Preset => None,
+                _ => Some(name.clone()),
+            },
+            name,
+            class,
+            shift: self.shift,
+            inputs: contiguous(self.inputs, &format!("{}_input", func))?,
+            buffers: optional(contiguous(self.buffers, &format!("{}_buffer", func))?),
+            params: optional(contiguous(self.params, &format!("{}_param", func))?),
+        }))
+    }
+}
+
+// the index of input3 for the prefix input
+fn indexed(field: &str, prefix: &str) -> Option<usize> {
+    field.strip_prefix(prefix)?.parse().ok()
+}
+
+fn contiguous<T>(values: BTreeMap<usize, T>, key: &str) -> Result<Vec<T>> {
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(expected, (i, v))| {
+            ensure!(i == expected, "{}{} is missing", key, expected);
+            Ok(v)
+        })
+        .collect()
+}
+
+fn optional<T>(values: Vec<T>) -> Option<Vec<T>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+fn number(s: &str) -> Result<BigDecimal> {
+    s.parse().map_err(|_| anyhow!("{:?} is not a number", s))
+}
+
+// The inverse of Input::to_param_str. Int inputs are written without decimals and double inputs
+// always with, values that aren't numbers are string inputs. An int range with the default 0 is
+// read as a range without a default.
+fn parse_input(s: &str) -> Result<Input> {
+    let fields = s.split("||").collect::<Vec<_>>();
+    ensure!(
+        fields.len() == 5,
+        "expected value||start||step||stop||Y but got {:?}",
+        s
+    );
+    let is_int = |fields: &[&str]| fields.iter().all(|f| !f.contains('.'));
+    match fields[4] {
+        "N" => Ok(match fields[0].parse::<BigDecimal>() {
+            Ok(value) => Input {
+                name: None,
+                kind: if is_int(&fields[..1]) {
+                    InputKind::Int
+                } else {
+                    InputKind::Double
+                },
+                range: InputRange::Fixed(InputValue::Number(value)),
+            },
+            Err(_) => Input {
+                name: None,
+                kind: InputKind::String,
+                range: InputRange::Fixed(InputValue::Text(fields[0].to_string())),
+            },
+        }),
+        "Y" => {
+            let (start, step, stop) = (number(fields[1])?, number(fields[2])?, number(fields[3])?);
+            Ok(Input {
+                name: None,
+                kind: if is_int(&fields[1..4]) {
+                    InputKind::Int
+                } else {
+                    InputKind::Double
+                },
+                range: if fields[0] == "0" {
+                    InputRange::Range { start, stop, step }
+                } else {
+                    InputRange::RangeWithDefault {
+                        default: number(fields[0])?,
+                        start,
+                        stop,
+                        step,
+                    }
+                },
+            })
+        }
+        o => bail!("expected Y or N to optimize but got {:?}", o),
+    }
+}
+
+pub fn parse_set_file(content: &str) -> Result<SetFile> {
+    let mut set_file = SetFile::default();
+    let mut indicators = HashMap::<IndiFunc, IndicatorLines>::new();
+    let mut symbols = BTreeMap::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        let mut parse_line = || -> Result<()> {
+            let mut kv = line.splitn(2, '=');
+            let key = kv.next().unwrap();
+            let value = kv.next().context("expected key=value")?;
+            let mut prefix_field = key.splitn(2, '_');
+            let (prefix, field) = (prefix_field.next().unwrap(), prefix_field.next());
+            match (prefix, field) {
+                ("Expert", Some("Title")) => set_file.name = Some(value.to_string()),
+                ("Expert", Some("Store_Results")) => {
+                    set_file.store_results = Some(match value {
+                        "0" => StoreResults::None,
+                        "1" => StoreResults::SideChanges,
+                        _ => bail!("unknown Store_Results {:?}", value),
+                    })
+                }
+                ("Expert", Some(field)) => match indexed(field, "symbol") {
+                    Some(i) => {
+                        symbols.insert(i, value.to_string());
+                    }
+                    None => debug!("ignoring {}", key),
+                },
+                (prefix, Some(field)) => match prefix.parse::<IndiFunc>() {
+                    Ok(func) => indicators.entry(func).or_default().set(field, value)?,
+                    Err(_) => debug!("ignoring {}", key),
+                },
+                _ => debug!("ignoring {}", key),
+            }
+            Ok(())
+        };
+        parse_line().context(format!("line {}: {}", n + 1, line))?;
+    }
+
+    set_file.symbols = contiguous(symbols, "Expert_symbol")?;
+    for (func, lines) in indicators {
+        if let Some(indi) = lines.into_indicator(func)? {
+            set_file.indi_set.insert(func, indi);
+        }
+    }
+    Ok(set_file)
+}
+
+// files saved by the MT5 GUI are UTF-16, the ones written by backtestd UTF-8
+pub fn read_mt5_file(file: &Path) -> Result<String> {
+    let bytes = fs::read(file).context(format!("reading {:?} failed", file))?;
+    Ok(if bytes.starts_with(&[0xff, 0xfe]) {
+        let utf16 = bytes[2..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect::<Vec<_>>();
+        String::from_utf16(&utf16)?
+    } else {
+        String::from_utf8(bytes)?
+            .trim_start_matches('\u{feff}')
+            .to_string()
+    })
+}
+
+// no command reads .set files yet, the parser is tested by the round trips
+#[allow(dead_code)]
+pub fn read_set_file(file: &Path) -> Result<SetFile> {
+    parse_set_file(&read_mt5_file(file)?).context(format!("parsing {:?} failed", file))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::{_vec_to_bigdecimal, _vec_vec_to_inputs, RunParams, ToParamString};
+
+    fn run() -> RunParams {
+        let mut run = RunParams::_new_test(0);
+        run.store_results = StoreResults::SideChanges;
+        let mut inputs =
+            _vec_vec_to_inputs(vec![vec![14.], vec![10., 20., 0.5], vec![15., 5., 25., 1.]]);
+        inputs.push(Input {
+            name: None,
+            kind: InputKind::Int,
+            range: InputRange::Range {
+                start: 10.into(),
+                stop: 20.into(),
+                step: 2.into(),
+            },
+        });
+        inputs.push(Input {
+            name: None,
+            kind: InputKind::Int,
+            range: InputRange::RangeWithDefault {
+                default: 3.into(),
+                start: 1.into(),
+                stop: 5.into(),
+                step: 1.into(),
+            },
+        });
+        inputs.push(Input {
+            name: None,
+            kind: InputKind::String,
+            range: InputRange::Fixed(InputValue::Text("ema".to_string())),
+        });
+        run.indi_set.insert(
+            IndiFunc::Confirm,
+            Indicator {
+                name: "Aroon_Up_Down".to_string(),
+                filename: Some("Aroon_Up_Down".to_string()),
+                class: SignalClass::TwoLinesCross,
+                inputs,
+                buffers: Some(vec![0, 1]),
+                params: Some(_vec_to_bigdecimal(vec![1., 4.5])),
+                shift: 1,
+            },
+        );
+        run.indi_set.insert(
+            IndiFunc::Exit,
+            Indicator {
+                name: "ma".to_string(),
+                filename: None,
+                class: SignalClass::Preset,
+                inputs: _vec_vec_to_inputs(vec![vec![20., 10., -1.]]),
+                buffers: None,
+                params: None,
+                shift: 0,
+            },
+        );
+        run
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let run = run();
+        let set_file = parse_set_file(&run.to_param_string()).unwrap();
+        assert_eq!(
+            set_file,
+            SetFile {
+                name: Some(run.name.clone()),
+                indi_set: run.indi_set.clone(),
+                symbols: run.symbols.clone(),
+                store_results: Some(run.store_results),
+            }
+        );
+
+        // only the indicators
+        let set_file = parse_set_file(&run.indi_set.to_param_string()).unwrap();
+        assert_eq!(set_file.indi_set, run.indi_set);
+        assert_eq!(set_file.name, None);
+        assert!(set_file.symbols.is_empty());
+
+        // write -> read -> write gives the same lines
+        let mut again = run.clone();
+        again.indi_set = set_file.indi_set;
+        let lines = |r: &RunParams| {
+            let mut lines = r.to_param_string_vec();
+            lines.sort();
+            lines
+        };
+        assert_eq!(lines(&again), lines(&run));
+    }
+
+    #[test]
+    fn gui_set_file_test() {
+        let content = "; saved automatically on 2020.05.01 12:00\n\
+                       Expert_symbol0=EURUSD\n\
+                       Expert_symbol1=AUDCAD\n\
+                       Expert_Lots=0.1||0.1||0.01||1.0||N\n\
+                       Confirm_Indicator=ma\n\
+                       Confirm_SignalClass=0\n\
+                       Confirm_Shift=0\n\
+                       Confirm_input0=14||10||1||30||Y\n\
+                       Confirm_input1=1.5||1.0||0.1||2.0||N\n\
+                       Confirm3_Indicator=\n\
+                       Confirm3_SignalClass=0\n";
+        let set_file = parse_set_file(content).unwrap();
+        assert_eq!(set_file.symbols, vec!["EURUSD", "AUDCAD"]);
+        assert_eq!(set_file.indi_set.len(), 1);
+        let confirm = &set_file.indi_set[&IndiFunc::Confirm];
+        assert_eq!(confirm.inputs[0].kind, InputKind::Int);
+        assert_eq!(
+            confirm.inputs[0].range,
+            InputRange::RangeWithDefault {
+                default: 14.into(),
+                start: 10.into(),
+                stop: 30.into(),
+                step: 1.into()
+            }
+        );
+        assert_eq!(confirm.inputs[1].kind, InputKind::Double);
+        assert!(!confirm.inputs[1].is_optimized());
+
+        // UTF-16 with BOM
+        let file = Path::new("/tmp/backtestd_gui_set_file_test.set");
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend(
+            content
+                .encode_utf16()
+                .flat_map(|c| c.to_le_bytes().to_vec()),
+        );
+        fs::write(file, bytes).unwrap();
+        assert_eq!(read_set_file(file).unwrap(), set_file);
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        let err = parse_set_file("Confirm_Indicator=ma\nConfirm_input0=14||10||1\n").unwrap_err();
+        assert!(format!("{:#}", err).starts_with("line 2: Confirm_input0=14||10||1: expected"));
+
+        let err = parse_set_file(
+            "Confirm_Indicator=ma\nConfirm_SignalClass=0\nConfirm_input1=1||0||0||0||N\n",
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Confirm_input0 is missing");
+
+        let err = parse_set_file("Confirm_Indicator=ma\n").unwrap_err();
+        assert_eq!(err.to_string(), "Confirm_SignalClass is missing");
+
+        assert!(parse_set_file("Confirm_SignalClass=99\n").is_err());
+        assert!(parse_set_file("Confirm_input0=1||0||0||0||X\n").is_err());
+    }
+}
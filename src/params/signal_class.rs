@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 // FIXME define values same as in MQL
-#[derive(Debug, PartialEq, PartialOrd, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, PartialOrd, Eq, Hash, Copy, Clone, Default, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum SignalClass {
+    #[default]
     Preset = 0,
     ZeroLineCross = 1,
     TwoLinesCross = 2,
@@ -23,8 +25,66 @@ pub enum SignalClass {
     TwoLinesSlopeChange = 16,
 }
 
-impl Default for SignalClass {
-    fn default() -> Self {
-        SignalClass::Preset
+impl SignalClass {
+    // the number of buffers of the indicator the class reads the signal from
+    pub fn min_buffers(&self) -> usize {
+        use SignalClass::*;
+        match self {
+            Preset => 0,
+            TwoLinesCross
+            | TwoLinesTwoLevelsCross
+            | TwoLinesColorChange
+            | BothLinesTwoLevelsCross
+            | BothLinesLevelCross
+            | SaturationLines
+            | BothLinesSaturationLevels
+            | TwoLinesSlopeChange => 2,
+            _ => 1,
+        }
+    }
+}
+
+// the SignalClass=<n> of a .set file
+impl TryFrom<u8> for SignalClass {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        use SignalClass::*;
+        [
+            Preset,
+            ZeroLineCross,
+            TwoLinesCross,
+            TwoLinesTwoLevelsCross,
+            TwoLevelsCross,
+            PriceCross,
+            PriceCrossInverted,
+            Semaphore,
+            TwoLinesColorChange,
+            ColorChange,
+            BothLinesTwoLevelsCross,
+            BothLinesLevelCross,
+            SaturationLevels,
+            SaturationLines,
+            BothLinesSaturationLevels,
+            SlopeChange,
+            TwoLinesSlopeChange,
+        ]
+        .iter()
+        .copied()
+        .find(|c| *c as u8 == v)
+        .ok_or_else(|| anyhow!("unknown signal class: {}", v))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_from_u8_test() {
+        for v in 0..=16 {
+            assert_eq!(SignalClass::try_from(v).unwrap() as u8, v);
+        }
+        assert!(SignalClass::try_from(17).is_err());
     }
 }
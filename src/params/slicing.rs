@@ -0,0 +1,197 @@
+use super::indi_func::IndiFunc;
+use super::indicator_set::IndicatorSet;
+use super::input::Input;
+
+use anyhow::{Context, Result};
+
+// an optimized input of a set with its number of values
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Cut {
+    func: IndiFunc,
+    input: usize,
+    len: u64,
+}
+
+impl IndicatorSet {
+    // sorted by func and input so the slices don't depend on the order of the HashMap
+    fn optimized_inputs(&self) -> Result<Vec<Cut>> {
+        let mut cuts = Vec::new();
+        for (func, indi) in self.iter() {
+            let lengths = indi
+                .count_input_length()
+                .context(format!("{} indicator {}", func, indi.name))?;
+            for (input, len) in lengths.into_iter().enumerate() {
+                if len > 1 {
+                    cuts.push(Cut {
+                        func: *func,
+                        input,
+                        len,
+                    });
+                }
+            }
+        }
+        cuts.sort_by_key(|c| (c.func.to_string(), c.input));
+        Ok(cuts)
+    }
+
+    fn with_input(&self, cut: &Cut, input: Input) -> Self {
+        let mut set = self.clone();
+        set.get_mut(&cut.func).unwrap().inputs[cut.input] = input;
+        set
+    }
+
+    // Cuts the longest input into as few slices of the same size as needed so every slice has at
+    // most target combinations. If cutting the longest input down to single values is not enough
+    // the slices are cut further on the next longest input.
+    pub fn slice_balanced(self, target: u64) -> Result<Vec<Self>> {
+        ensure!(target > 0, "the target number of passes is zero");
+        let cnt = self.count_inputs_crossed()?;
+        if cnt <= target {
+            return Ok(vec![self]);
+        }
+        let cuts = self.optimized_inputs()?;
+        let longest = *cuts
+            .iter()
+            .max_by_key(|c| c.len)
+            .context("nothing to slice")?;
+
+        // the combinations of all other inputs
+        let rest = cuts
+            .iter()
+            .filter(|c| **c != longest)
+            .fold(1u64, |prod, c| prod.saturating_mul(c.len));
+        let per_slice = (target / rest).max(1);
+        let parts = longest.len.div_ceil(per_slice);
+        debug!(
+            "cutting {} input{} into {} slices",
+            longest.func, longest.input, parts
+        );
+
+        let mut sets = Vec::new();
+        for input in self[&longest.func].inputs[longest.input].chunks(parts)? {
+            sets.extend(self.with_input(&longest, input).slice_balanced(target)?);
+        }
+        Ok(sets)
+    }
+
+    // Cuts several inputs at once, always the one with the longest slices next, until every slice
+    // has at most target combinations. The slices are all combinations of the cut inputs.
+    pub fn slice_spread(self, target: u64) -> Result<Vec<Self>> {
+        ensure!(target > 0, "the target number of passes is zero");
+        if self.count_inputs_crossed()? <= target {
+            return Ok(vec![self]);
+        }
+        let cuts = self.optimized_inputs()?;
+        let mut parts = vec![1u64; cuts.len()];
+        loop {
+            let sizes = cuts
+                .iter()
+                .zip(&parts)
+                .map(|(c, p)| c.len.div_ceil(*p))
+                .collect::<Vec<_>>();
+            if sizes.iter().fold(1u64, |prod, s| prod.saturating_mul(*s)) <= target {
+                break;
+            }
+            let (k, size) = sizes
+                .into_iter()
+                .enumerate()
+                .max_by_key(|(_, size)| *size)
+                .context("nothing to slice")?;
+            // the least number of parts that makes the slices of input k shorter
+            parts[k] = (cuts[k].len + size - 2) / (size - 1);
+        }
+
+        let mut sets = vec![self];
+        for (cut, parts) in cuts.iter().zip(parts) {
+            if parts == 1 {
+                continue;
+            }
+            debug!(
+                "cutting {} input{} into {} slices",
+                cut.func, cut.input, parts
+            );
+            let mut cut_sets = Vec::with_capacity(sets.len() * parts as usize);
+            for set in &sets {
+                for input in set[&cut.func].inputs[cut.input].chunks(parts)? {
+                    cut_sets.push(set.with_input(cut, input));
+                }
+            }
+            sets = cut_sets;
+        }
+        Ok(sets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::indi_func::IndiFunc::*;
+    use crate::params::indicator::Indicator;
+
+    // 11 * 21 combinations
+    fn set() -> IndicatorSet {
+        let mut set = IndicatorSet::_new_test(2);
+        set.get_mut(&Confirm).unwrap().inputs = _vec_vec_to_inputs(vec![vec![10., 20., 1.]]);
+        set.get_mut(&Confirm2).unwrap().inputs =
+            _vec_vec_to_inputs(vec![vec![3.], vec![10., 20., 0.5]]);
+        set
+    }
+
+    fn counts(sets: &[IndicatorSet]) -> Vec<u64> {
+        sets.iter()
+            .map(|s| s.count_inputs_crossed().unwrap())
+            .collect()
+    }
+
+    fn input_counts(sets: &[IndicatorSet], func: IndiFunc, input: usize) -> Vec<u64> {
+        sets.iter()
+            .map(|s| s[&func].inputs[input].count().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn slice_balanced_test() {
+        assert_eq!(set().slice_balanced(231).unwrap(), vec![set()]);
+
+        let slices = set().slice_balanced(100).unwrap();
+        assert_eq!(counts(&slices), vec![77, 77, 77]);
+        assert_eq!(input_counts(&slices, Confirm2, 1), vec![7, 7, 7]);
+        assert_eq!(
+            IndicatorSet::merge_ranges(&slices.iter().collect::<Vec<_>>()),
+            Some(set())
+        );
+
+        // the longest input alone is not enough
+        let slices = set().slice_balanced(5).unwrap();
+        assert_eq!(slices.len(), 21 * 3);
+        assert!(counts(&slices).iter().all(|c| *c <= 5));
+        assert_eq!(counts(&slices).iter().sum::<u64>(), 231);
+
+        assert!(set().slice_balanced(0).is_err());
+    }
+
+    #[test]
+    fn slice_spread_test() {
+        assert_eq!(set().slice_spread(231).unwrap(), vec![set()]);
+
+        let slices = set().slice_spread(50).unwrap();
+        assert_eq!(slices.len(), 2 * 3);
+        assert!(counts(&slices).iter().all(|c| *c <= 50));
+        assert_eq!(counts(&slices).iter().sum::<u64>(), 231);
+        // both inputs are cut
+        assert_eq!(input_counts(&slices, Confirm, 0), vec![5, 5, 5, 6, 6, 6]);
+        assert_eq!(input_counts(&slices, Confirm2, 1), vec![7; 6]);
+        assert_eq!(
+            IndicatorSet::merge_ranges(&slices.iter().collect::<Vec<_>>()),
+            Some(set())
+        );
+
+        let slices = set().slice_spread(1).unwrap();
+        assert_eq!(slices.len(), 231);
+
+        let mut set = set();
+        set.insert(Exit, Indicator::_new_test(Exit, 1));
+        assert_eq!(set.clone().slice_spread(300).unwrap(), vec![set]);
+    }
+}
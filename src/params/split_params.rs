@@ -0,0 +1,89 @@
+use super::indicator_set::IndicatorSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+// how a run is split into a queue of runs before it is executed
+#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+pub enum SplitStrategy {
+    // execute the run as it is
+    None,
+    // slice the inputs if there are too many combinations
+    Slices,
+    // split into one run per Symbol if there are many combinations
+    Symbols,
+    // both of the above
+    #[default]
+    SlicesAndSymbols,
+}
+
+// how the inputs of a run with too many combinations are sliced
+#[derive(Debug, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+pub enum SliceStrategy {
+    // halve the longest input until every slice is below max_combinations
+    #[default]
+    Halve,
+    // cut the longest input into as few slices of passes_per_run as needed
+    Balanced,
+    // cut several inputs at once into slices of passes_per_run
+    Spread,
+    // balanced slices of run_limit_multi_currency that test all Symbols at once
+    MultiCurrency,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SplitParams {
+    pub strategy: SplitStrategy,
+    pub slicing: SliceStrategy,
+    // MT5 forces genetic optimization if there are more than 100M possibilities
+    pub max_combinations: u64,
+    // the target number of passes of a Balanced or Spread slice. At most max_combinations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passes_per_run: Option<u64>,
+    // above this number of crossed inputs every Symbol is tested in a separate run
+    pub run_limit_multi_currency: u64,
+}
+
+impl Default for SplitParams {
+    fn default() -> Self {
+        SplitParams {
+            strategy: SplitStrategy::default(),
+            slicing: SliceStrategy::default(),
+            max_combinations: 100_000_000,
+            passes_per_run: None,
+            run_limit_multi_currency: crate::RUN_LIMIT_MULTI_CURRENCY,
+        }
+    }
+}
+
+impl SplitParams {
+    pub fn slices(&self) -> bool {
+        matches!(
+            self.strategy,
+            SplitStrategy::Slices | SplitStrategy::SlicesAndSymbols
+        )
+    }
+
+    pub fn symbols(&self) -> bool {
+        matches!(
+            self.strategy,
+            SplitStrategy::Symbols | SplitStrategy::SlicesAndSymbols
+        )
+    }
+
+    // slices set according to the slicing strategy
+    pub fn slice(&self, set: IndicatorSet) -> Result<Vec<IndicatorSet>> {
+        let target = self
+            .passes_per_run
+            .map_or(self.max_combinations, |p| p.min(self.max_combinations));
+        match self.slicing {
+            SliceStrategy::Halve => set.slice_recursive(self.max_combinations),
+            SliceStrategy::Balanced => set.slice_balanced(target),
+            SliceStrategy::Spread => set.slice_spread(target),
+            SliceStrategy::MultiCurrency => {
+                set.slice_balanced(self.run_limit_multi_currency.min(self.max_combinations))
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use super::common_params::{default_journal, CommonParams};
+use super::{BacktestModel, OptimizeCrit, OptimizeMode};
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+type Section = HashMap<String, String>;
+
+// The settings of [Tester] that belong to a run. The field names are the ones of RunParams so
+// they can be copied into a run config.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct TesterSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtest_model: Option<BacktestModel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimize: Option<OptimizeMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimize_crit: Option<OptimizeCrit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visual: Option<bool>,
+    // the ini only has the Symbol the report is named after
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<String>,
+}
+
+// the keys of every [section]. Comments and keys outside of a section are ignored
+fn parse_ini(ini: &str) -> HashMap<String, Section> {
+    let mut sections = HashMap::new();
+    let mut current = None;
+    for line in ini.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].to_string();
+            sections.entry(name.clone()).or_insert_with(Section::new);
+            current = Some(name);
+        } else if let (Some(section), Some(eq)) = (&current, line.find('=')) {
+            sections.get_mut(section).unwrap().insert(
+                line[..eq].trim().to_string(),
+                line[eq + 1..].trim().to_string(),
+            );
+        }
+    }
+    sections
+}
+
+fn get<'a>(section: &'a Section, key: &str) -> Result<&'a str> {
+    section
+        .get(key)
+        .map(String::as_str)
+        .context(format!("{} is missing", key))
+}
+
+fn parse<T>(section: &Section, key: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let value = get(section, key)?;
+    value
+        .parse()
+        .context(format!("{}={} is invalid", key, value))
+}
+
+fn flag(section: &Section, key: &str) -> Result<bool> {
+    match get(section, key)? {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        v => bail!("{}={} is not 0 or 1", key, v),
+    }
+}
+
+// the enums are written as their number
+fn repr<T: DeserializeOwned>(section: &Section, key: &str) -> Result<T> {
+    let v = parse::<u8>(section, key)?;
+    serde_json::from_value(v.into()).context(format!("{}={} is unknown", key, v))
+}
+
+fn date(section: &Section, key: &str) -> Result<DateTime<Utc>> {
+    let value = get(section, key)?;
+    let date = NaiveDate::parse_from_str(value, "%Y.%m.%d")
+        .context(format!("{}={} is not a yyyy.mm.dd date", key, value))?;
+    let midnight = date
+        .and_hms_opt(0, 0, 0)
+        .context(format!("{}={} has no midnight", key, value))?;
+    Ok(Utc.from_utc_datetime(&midnight))
+}
+
+// None if the key is missing
+fn optional<T, F>(section: &Section, key: &str, f: F) -> Result<Option<T>>
+where
+    F: Fn(&Section, &str) -> Result<T>,
+{
+    match section.get(key) {
+        Some(_) => f(section, key).map(Some),
+        None => Ok(None),
+    }
+}
+
+// The inverse of to_terminal_config. The paths to the terminal aren't part of the ini. All
+// settings that aren't in the ini get their defaults.
+pub fn import_terminal_ini(
+    ini: &str,
+    terminal_exe: PathBuf,
+    workdir: PathBuf,
+) -> Result<(CommonParams, TesterSettings)> {
+    let sections = parse_ini(ini);
+    let tester = sections
+        .get("Tester")
+        .context("the [Tester] section is missing")?;
+    let login = match (tester.get("Login"), sections.get("Common")) {
+        (Some(login), _) => login.clone(),
+        (None, Some(common)) => get(common, "Login").context("[Common]")?.to_string(),
+        (None, None) => bail!("Login is missing"),
+    };
+
+    let read_common = || -> Result<CommonParams> {
+        // reports\<name>_<symbol>.xml
+        let reports = match tester.get("Report") {
+            Some(report) => {
+                let mut parts = report.split(['\\', '/']).collect::<Vec<_>>();
+                parts.pop();
+                parts.into_iter().collect()
+            }
+            None => PathBuf::from("reports"),
+        };
+        Ok(CommonParams {
+            params_file: get(tester, "ExpertParameters")?.to_string(),
+            wine: false,
+            wineprefix: None,
+            terminal_exe,
+            workdir,
+            reports,
+            expert: get(tester, "Expert")?.to_string(),
+            period: get(tester, "Period")?.to_string(),
+            login,
+            use_remote: flag(tester, "UseRemote")?,
+            use_local: flag(tester, "UseLocal")?,
+            replace_report: flag(tester, "ReplaceReport")?,
+            shutdown_terminal: flag(tester, "ShutdownTerminal")?,
+            deposit: parse(tester, "Deposit")?,
+            currency: get(tester, "Currency")?.to_string(),
+            // the MT5 GUI writes 1:100
+            leverage: get(tester, "Leverage")?
+                .trim_start_matches("1:")
+                .parse()
+                .context(format!("Leverage={} is invalid", tester["Leverage"]))?,
+            execution_mode: parse(tester, "ExecutionMode")?,
+            terminals: Vec::new(),
+            split: Default::default(),
+            timeouts: Default::default(),
+            journal: default_journal(),
+        })
+    };
+    let common = read_common().context("[Tester]")?;
+
+    let settings = TesterSettings {
+        date: match (
+            optional(tester, "FromDate", date)?,
+            optional(tester, "ToDate", date)?,
+        ) {
+            (Some(from), Some(to)) => Some((from, to)),
+            _ => None,
+        },
+        backtest_model: optional(tester, "Model", repr)?,
+        optimize: optional(tester, "Optimization", repr)?,
+        optimize_crit: optional(tester, "OptimizationCriterion", repr)?,
+        visual: optional(tester, "Visual", flag)?,
+        symbols: tester.get("Symbol").into_iter().cloned().collect(),
+    };
+    Ok((common, settings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::{to_terminal_config, RunParams};
+
+    #[test]
+    fn round_trip_test() {
+        let common = CommonParams::_new_test();
+        let mut run = RunParams::_new_test(1);
+        run.visual = true;
+        run.optimize = OptimizeMode::Genetic;
+        let ini = to_terminal_config(&common, &run).unwrap();
+
+        let (imported, settings) =
+            import_terminal_ini(&ini, common.terminal_exe.clone(), common.workdir.clone()).unwrap();
+        assert_eq!(imported, common);
+        assert_eq!(
+            settings,
+            TesterSettings {
+                date: Some(run.date),
+                backtest_model: Some(run.backtest_model),
+                optimize: Some(OptimizeMode::Genetic),
+                optimize_crit: Some(run.optimize_crit),
+                visual: Some(true),
+                symbols: vec!["USDCHF".to_string()],
+            }
+        );
+        assert_eq!(
+            to_terminal_config(&imported, &run).unwrap(),
+            to_terminal_config(&common, &run).unwrap()
+        );
+    }
+
+    #[test]
+    fn gui_ini_test() {
+        let ini = "; saved by the strategy tester\n\
+                   [Common]\n\
+                   Login=5555\n\
+                   [Tester]\n\
+                   Expert=Advisors\\backtestd\\expert.ex5\n\
+                   ExpertParameters=expert.set\n\
+                   Period=H4\n\
+                   UseLocal=1\n\
+                   UseRemote=0\n\
+                   ReplaceReport=1\n\
+                   ShutdownTerminal=0\n\
+                   Deposit=5000\n\
+                   Currency=EUR\n\
+                   Leverage=1:500\n\
+                   ExecutionMode=0\n\
+                   Model=1\n";
+        let (common, settings) =
+            import_terminal_ini(ini, PathBuf::from("terminal64.exe"), PathBuf::from("mt5"))
+                .unwrap();
+        assert_eq!(common.login, "5555");
+        assert_eq!(common.leverage, 500);
+        assert_eq!(common.reports, PathBuf::from("reports"));
+        assert!(!common.shutdown_terminal);
+        assert_eq!(
+            settings,
+            TesterSettings {
+                backtest_model: Some(BacktestModel::OneMinuteOHLC),
+                ..Default::default()
+            }
+        );
+
+        let err = import_terminal_ini(
+            &ini.replace("Period=H4\n", ""),
+            PathBuf::new(),
+            PathBuf::new(),
+        )
+        .unwrap_err();
+        assert_eq!(format!("{:#}", err), "[Tester]: Period is missing");
+
+        let err = import_terminal_ini(
+            &ini.replace("Model=1", "Model=9"),
+            PathBuf::new(),
+            PathBuf::new(),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Model=9 is unknown"));
+        assert!(
+            import_terminal_ini("[Common]\nLogin=1\n", PathBuf::new(), PathBuf::new()).is_err()
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+// supervision of the terminal process. all durations are in seconds
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TimeoutParams {
+    // wall-clock limit for a single run of the terminal
+    pub run: Option<u64>,
+    // limit for the time the tester log does not grow
+    pub inactivity: Option<u64>,
+    // how often a run is restarted after hitting a timeout
+    pub retries: u32,
+}
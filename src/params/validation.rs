@@ -0,0 +1,202 @@
+use super::indi_func::IndiFunc;
+use super::indicator::Indicator;
+use super::input::{InputKind, InputRange, InputValue};
+use super::run_params::RunParams;
+use super::OptimizeMode;
+
+use serde::Serialize;
+use std::fmt;
+
+// a mistake in a run that would only show up after the terminal was started
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Problem {
+    pub field: String,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(field: &str, message: String) -> Self {
+        Problem {
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+// all problems of a run
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ValidationError(pub Vec<Problem>);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} problem(s) in the run", self.0.len())?;
+        for p in &self.0 {
+            write!(f, "\n  {}", p)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Indicator {
+    pub fn problems(&self, func: IndiFunc) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        for (i, input) in self.inputs.iter().enumerate() {
+            let field = format!("indi_set.{}.inputs[{}]", func, i);
+            if let InputRange::Fixed(InputValue::Text(t)) = &input.range {
+                if input.kind != InputKind::String {
+                    problems.push(Problem::new(
+                        &field,
+                        format!("the text {:?} is not a number", t),
+                    ));
+                }
+            }
+            if let Some((start, stop, step)) = input.range.bounds() {
+                match input.kind {
+                    InputKind::String => problems.push(Problem::new(
+                        &field,
+                        "a string input can't be optimized".to_string(),
+                    )),
+                    InputKind::Int
+                        if [start, stop, step].iter().any(|v| v.with_scale(0) != **v) =>
+                    {
+                        problems.push(Problem::new(
+                            &field,
+                            "the range of an int input has decimals".to_string(),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+            if let Err(e) = input.count() {
+                problems.push(Problem::new(&field, format!("{:#}", e)));
+            }
+        }
+
+        let buffers = self.buffers.as_ref().map_or(0, |b| b.len());
+        if buffers < self.class.min_buffers() {
+            problems.push(Problem::new(
+                &format!("indi_set.{}.buffers", func),
+                format!(
+                    "{:?} needs {} buffer(s) but {} are configured",
+                    self.class,
+                    self.class.min_buffers(),
+                    buffers
+                ),
+            ));
+        }
+        problems
+    }
+}
+
+impl RunParams {
+    // returns all problems at once
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut problems = Vec::new();
+        if self.name.is_empty() {
+            problems.push(Problem::new("name", "is empty".to_string()));
+        }
+        if self.symbols.is_empty() {
+            problems.push(Problem::new("symbols", "no symbol to test".to_string()));
+        }
+        if self.date.0 >= self.date.1 {
+            problems.push(Problem::new(
+                "date",
+                format!("{} is not before {}", self.date.0, self.date.1),
+            ));
+        } else if let Some(wf) = &self.walk_forward {
+            if let Err(e) = wf.windows(&self.date) {
+                problems.push(Problem::new("walk_forward", format!("{:#}", e)));
+            }
+        }
+        if self.visual && self.optimize != OptimizeMode::Disabled {
+            problems.push(Problem::new(
+                "visual",
+                "the visual mode can't be used with optimization".to_string(),
+            ));
+        }
+
+        let mut funcs = self.indi_set.keys().collect::<Vec<_>>();
+        funcs.sort_by_key(|f| f.to_string());
+        for func in funcs {
+            problems.extend(self.indi_set[func].problems(*func));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::signal_class::SignalClass;
+    use chrono::Duration;
+
+    #[test]
+    fn validate_test() {
+        let run = RunParams::_new_test(3);
+        assert_eq!(run.validate(), Ok(()));
+
+        let mut run = RunParams::_new_test(1);
+        run.symbols.clear();
+        run.date.1 = run.date.0 - Duration::days(1);
+        run.visual = true;
+        run.indi_set.insert(
+            IndiFunc::Baseline,
+            Indicator {
+                name: "kijunsen".to_string(),
+                filename: Some("Kijunsen".to_string()),
+                shift: 0,
+                inputs: _vec_vec_to_inputs(vec![
+                    vec![1., 2.5, 1.],
+                    vec![10., 5., 1.],
+                    vec![5., 1., 10., 0.],
+                    vec![3.],
+                    vec![20., 10., -0.5],
+                    vec![1., 10., -1.],
+                ]),
+                buffers: None,
+                params: None,
+                class: SignalClass::TwoLinesCross,
+            },
+        );
+        run.indi_set.get_mut(&IndiFunc::Baseline).unwrap().inputs[0].kind = InputKind::Int;
+
+        let problems = run.validate().unwrap_err().0;
+        assert_eq!(
+            problems
+                .iter()
+                .map(|p| p.field.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "symbols",
+                "date",
+                "visual",
+                "indi_set.Baseline.inputs[0]",
+                "indi_set.Baseline.inputs[1]",
+                "indi_set.Baseline.inputs[2]",
+                "indi_set.Baseline.inputs[5]",
+                "indi_set.Baseline.buffers",
+            ]
+        );
+        assert!(problems[3].message.contains("decimals"));
+        assert!(problems[4].message.contains("greater than"));
+        assert!(problems[5].message.contains("zero"));
+        assert!(problems[6].message.contains("negative"));
+
+        let err = ValidationError(problems).to_string();
+        assert!(err.starts_with("8 problem(s) in the run\n  symbols: "));
+    }
+}
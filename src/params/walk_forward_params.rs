@@ -0,0 +1,101 @@
+use crate::results::best::RankParams;
+
+use anyhow::Result;
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+// Walk-forward analysis of a run. The date range of the run is divided into windows of an
+// in-sample optimization followed by an out-of-sample test. All lengths are in days.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WalkForwardParams {
+    pub in_sample: i64,
+    pub out_of_sample: i64,
+    // shift between two windows. Defaults to out_of_sample so the out-of-sample periods are
+    // adjacent
+    #[serde(default)]
+    pub step: Option<i64>,
+    // how the best pass of an in-sample optimization is picked. top is ignored
+    #[serde(default)]
+    pub rank: RankParams,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WalkForwardWindow {
+    pub in_sample: (DateTime<Utc>, DateTime<Utc>),
+    pub out_of_sample: (DateTime<Utc>, DateTime<Utc>),
+}
+
+impl WalkForwardParams {
+    // all windows that fit into date
+    pub fn windows(&self, date: &(DateTime<Utc>, DateTime<Utc>)) -> Result<Vec<WalkForwardWindow>> {
+        let step = self.step.unwrap_or(self.out_of_sample);
+        ensure!(
+            self.in_sample > 0 && self.out_of_sample > 0 && step > 0,
+            "walk-forward windows need a positive length and step"
+        );
+
+        let mut windows = Vec::new();
+        let mut start = date.0;
+        loop {
+            let split = start + Duration::days(self.in_sample);
+            let end = split + Duration::days(self.out_of_sample);
+            if end > date.1 {
+                break;
+            }
+            windows.push(WalkForwardWindow {
+                in_sample: (start, split),
+                out_of_sample: (split, end),
+            });
+            start += Duration::days(step);
+        }
+        ensure!(
+            !windows.is_empty(),
+            "the date range {} - {} is shorter than a walk-forward window of {} days",
+            date.0,
+            date.1,
+            self.in_sample + self.out_of_sample
+        );
+        Ok(windows)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().into()
+    }
+
+    #[test]
+    fn windows_test() {
+        let range = (
+            date("2017-01-01T00:00:00-00:00"),
+            date("2018-01-01T00:00:00-00:00"),
+        );
+        let mut wf = WalkForwardParams {
+            in_sample: 180,
+            out_of_sample: 60,
+            step: None,
+            rank: RankParams::default(),
+        };
+
+        let windows = wf.windows(&range).unwrap();
+        // 180 + 4 * 60 > 365
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].in_sample.0, range.0);
+        assert_eq!(windows[0].in_sample.1, windows[0].out_of_sample.0);
+        assert_eq!(windows[0].out_of_sample.1, windows[1].out_of_sample.0);
+        assert!(windows[2].out_of_sample.1 <= range.1);
+
+        wf.step = Some(30);
+        assert_eq!(wf.windows(&range).unwrap().len(), 5);
+
+        wf.in_sample = 400;
+        assert!(wf.windows(&range).is_err());
+        wf.in_sample = 180;
+        wf.step = Some(0);
+        assert!(wf.windows(&range).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::backtest_runner::is_cancelled;
+use crate::params::indicator::Indicator;
+use crate::params::indicator_set_files::read_indicator_catalog;
+use crate::params::*;
+use crate::results::best::{best_passes, RankParams};
+use crate::results::csv_reader::read_results_csv;
+use crate::results::ResultRow;
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// an indicator set that made it through a stage
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Candidate {
+    // the name of the run the candidate was scored by
+    pub run: String,
+    // the catalog file of the indicator that was tested in the stage
+    pub file: PathBuf,
+    pub value: f64,
+    pub best: ResultRow,
+    // the indicators of all stages so far with the inputs of the best pass
+    pub indi_set: IndicatorSet,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct StageResult {
+    pub stage: usize,
+    pub func: IndiFunc,
+    // best first, at most keep
+    pub kept: Vec<Candidate>,
+    // runs that failed or had no qualifying pass and the reason
+    pub failed: Vec<(String, String)>,
+}
+
+impl StageResult {
+    fn file(dir: &Path, stage: usize, func: IndiFunc) -> PathBuf {
+        dir.join(format!(
+            "stage{}_{}.json",
+            stage,
+            func.to_string().to_lowercase()
+        ))
+    }
+}
+
+// <reports>/<name>_pipeline
+pub fn pipeline_dir(reports: &Path, params: &PipelineParams) -> PathBuf {
+    reports.join(format!("{}_pipeline", params.name))
+}
+
+// Executes the stages of the pipeline on top of base. Every candidate of a stage is run on every
+// set kept by the previous stage. execute runs a single logical run and returns the path to its
+// merged result csv. The result of every stage is written to dir, stages with an existing result
+// are not executed again.
+pub fn run_pipeline<F>(
+    params: &PipelineParams,
+    base: &RunParams,
+    dir: &Path,
+    mut execute: F,
+) -> Result<Vec<StageResult>>
+where
+    F: FnMut(&RunParams) -> Result<PathBuf>,
+{
+    ensure!(!params.stages.is_empty(), "the pipeline has no stages");
+    fs::create_dir_all(dir).context(format!("creating {:?} failed", dir))?;
+    let rank = RankParams {
+        top: 1,
+        ..params.rank.clone()
+    };
+
+    let mut results = Vec::<StageResult>::with_capacity(params.stages.len());
+    for (i, stage) in params.stages.iter().enumerate() {
+        let file = StageResult::file(dir, i, stage.func);
+        if file.exists() {
+            let result: StageResult = serde_json::from_str(&fs::read_to_string(&file)?)
+                .context(format!("reading stage result {:?} failed", file))?;
+            info!("stage {} ({}) already done", i, stage.func);
+            results.push(result);
+            continue;
+        }
+
+        let carried = match results.last() {
+            Some(r) => r.kept.iter().map(|c| c.indi_set.clone()).collect(),
+            None => vec![base.indi_set.clone()],
+        };
+        let catalog = read_indicator_catalog(&stage.catalog)?;
+        ensure!(
+            !catalog.is_empty(),
+            "the catalog {:?} of stage {} is empty",
+            stage.catalog,
+            i
+        );
+        info!(
+            "stage {} ({}): {} candidates on {} sets",
+            i,
+            stage.func,
+            catalog.len(),
+            carried.len()
+        );
+
+        let mut result = StageResult {
+            stage: i,
+            func: stage.func,
+            kept: Vec::new(),
+            failed: Vec::new(),
+        };
+        for (k, set) in carried.iter().enumerate() {
+            for candidate in &catalog {
+                let run = stage_run(params, base, i, stage.func, k, set, candidate);
+                match score_candidate(&run, &candidate.0, &rank, &mut execute) {
+                    Ok(c) => result.kept.push(c),
+                    // the results of the finished stages are kept for the next start
+                    Err(e) if is_cancelled(&e) => return Err(e),
+                    Err(e) => {
+                        warn!("{} failed: {:?}", run.name, e);
+                        result.failed.push((run.name, format!("{:#}", e)));
+                    }
+                }
+            }
+        }
+        result
+            .kept
+            .sort_by(|a, b| b.value.partial_cmp(&a.value).unwrap());
+        result.kept.truncate(stage.keep);
+        ensure!(
+            !result.kept.is_empty(),
+            "no candidate of stage {} ({}) has a result",
+            i,
+            stage.func
+        );
+
+        fs::write(&file, serde_json::to_string_pretty(&result)?)
+            .context(format!("writing stage result {:?} failed", file))?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn stage_run(
+    params: &PipelineParams,
+    base: &RunParams,
+    stage: usize,
+    func: IndiFunc,
+    k: usize,
+    set: &IndicatorSet,
+    (file, indi): &(PathBuf, Indicator),
+) -> RunParams {
+    let mut indi_set = set.clone();
+    indi_set.insert(func, indi.clone());
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    RunParams {
+        name: format!(
+            "{}_s{}_{}_{}_{}",
+            params.name,
+            stage,
+            func.to_string().to_lowercase(),
+            stem,
+            k
+        ),
+        indi_set,
+        walk_forward: None,
+        ..base.clone()
+    }
+}
+
+fn score_candidate<F>(
+    run: &RunParams,
+    file: &Path,
+    rank: &RankParams,
+    execute: &mut F,
+) -> Result<Candidate>
+where
+    F: FnMut(&RunParams) -> Result<PathBuf>,
+{
+    let (header, rows) = read_results_csv(&execute(run)?)?;
+    let best = best_passes(&header, &rows, &run.indi_set, rank)?
+        .pop()
+        .context("no pass qualifies as best")?;
+    Ok(Candidate {
+        run: run.name.clone(),
+        file: file.to_path_buf(),
+        value: best.value,
+        indi_set: header.indicator_set(&run.indi_set, &best.row.params)?,
+        best: best.row,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::pipeline_params::PipelineStage;
+    use crate::params::signal_class::SignalClass;
+    use crate::results::best::RankMetric;
+    use bigdecimal::ToPrimitive;
+
+    fn indicator(name: &str, inputs: Vec<Vec<f32>>) -> Indicator {
+        Indicator {
+            name: name.to_string(),
+            filename: None,
+            shift: 0,
+            inputs: _vec_vec_to_inputs(inputs),
+            buffers: None,
+            params: None,
+            class: SignalClass::Preset,
+        }
+    }
+
+    // a report with one pass per value of the optimized input. The custom metric is the
+    // value itself for "good" indicators and its negative otherwise
+    fn write_report(r: &RunParams, csv: &Path) -> Result<()> {
+        let mut header = "Pass,Result,Profit,Expected Payoff,Profit Factor,Recovery Factor,\
+                          Sharpe Ratio,Custom,Equity DD %,Trades"
+            .to_string();
+        let mut optimized = None;
+        for (func, indi) in r.indi_set.iter() {
+            for (i, input) in indi.inputs.iter().enumerate() {
+                if input.is_optimized() {
+                    header += &format!(",{}_double{}", func, i);
+                    optimized = Some((indi.name.clone(), input.clone()));
+                }
+            }
+        }
+        let (name, input) = optimized.context("nothing to optimize")?;
+        ensure!(name != "broken", "terminal crashed");
+        let sign = if name.starts_with("good") { 1 } else { -1 };
+        let mut content = header + "\n";
+        let (start, stop, _) = input.range.bounds().unwrap();
+        for v in start.to_i32().unwrap()..=stop.to_i32().unwrap() {
+            content += &format!("{},0,0,0,0,0,0,{},0,10,{}\n", v, sign * v, v);
+        }
+        fs::write(csv, content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn run_pipeline_test() {
+        let dir = Path::new("/tmp/backtestd_pipeline_test");
+        let _ = fs::remove_dir_all(dir);
+        for (func, names) in &[
+            ("baseline", vec!["good_ma", "bad_ma", "broken"]),
+            ("confirm", vec!["good_aroon", "bad_aroon"]),
+        ] {
+            let catalog = dir.join("catalog").join(func);
+            fs::create_dir_all(&catalog).unwrap();
+            for name in names {
+                serde_any::to_file(
+                    catalog.join(format!("{}.yaml", name)),
+                    &indicator(name, vec![vec![1., 3., 1.]]),
+                )
+                .unwrap();
+            }
+        }
+
+        let params = PipelineParams {
+            name: "nnfx".to_string(),
+            run: PathBuf::from("run.yaml"),
+            stages: vec![
+                PipelineStage {
+                    func: IndiFunc::Baseline,
+                    catalog: dir.join("catalog/baseline"),
+                    keep: 2,
+                },
+                PipelineStage {
+                    func: IndiFunc::Confirm,
+                    catalog: dir.join("catalog/confirm"),
+                    keep: 1,
+                },
+            ],
+            rank: RankParams {
+                metric: RankMetric::Custom,
+                ..Default::default()
+            },
+        };
+        let mut base = RunParams::_new_test(0);
+        base.indi_set
+            .insert(IndiFunc::Exit, indicator("exit", vec![vec![5.]]));
+
+        let out = dir.join("out");
+        let mut executed = Vec::new();
+        let mut execute = |r: &RunParams| {
+            executed.push(r.name.clone());
+            let csv = out.join(&r.name).with_extension("csv");
+            write_report(r, &csv)?;
+            Ok(csv)
+        };
+        let results = run_pipeline(&params, &base, &out, &mut execute).unwrap();
+
+        assert_eq!(results.len(), 2);
+        // 3 baselines, then 2 confirms on each of the 2 kept baselines
+        assert_eq!(executed.len(), 3 + 2 * 2);
+        assert!(executed.contains(&"nnfx_s0_baseline_good_ma_0".to_string()));
+        assert!(executed.contains(&"nnfx_s1_confirm_good_aroon_1".to_string()));
+
+        let s0 = &results[0];
+        assert_eq!(s0.kept.len(), 2);
+        assert_eq!(s0.failed.len(), 1);
+        assert_eq!(s0.kept[0].file, dir.join("catalog/baseline/good_ma.yaml"));
+        assert_eq!(s0.kept[0].value, 3.);
+        // the winner is pinned to the inputs of its best pass
+        let ma = &s0.kept[0].indi_set[&IndiFunc::Baseline];
+        assert_eq!(ma.inputs, _vec_vec_to_inputs(vec![vec![3.]]));
+        assert!(s0.kept[0].indi_set.contains_key(&IndiFunc::Exit));
+
+        let s1 = &results[1];
+        assert_eq!(s1.kept.len(), 1);
+        let set = &s1.kept[0].indi_set;
+        assert_eq!(set[&IndiFunc::Baseline].name, "good_ma");
+        assert_eq!(set[&IndiFunc::Confirm].name, "good_aroon");
+        assert_eq!(set.count_inputs_crossed().unwrap(), 1);
+        assert!(out.join("stage0_baseline.json").exists());
+        assert!(out.join("stage1_confirm.json").exists());
+
+        // resuming skips the finished stages
+        let mut resumed = 0;
+        let again = run_pipeline(&params, &base, &out, |_| {
+            resumed += 1;
+            bail!("should not run")
+        })
+        .unwrap();
+        assert_eq!(resumed, 0);
+        assert_eq!(again, results);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::params::*;
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// the files the terminal would be started with for one run of the queue
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PlannedRun {
+    pub name: String,
+    pub symbols: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slice: Option<usize>,
+    pub passes: u64,
+    pub terminal_ini: String,
+    // the content of the .set file with the indicator inputs
+    pub params: String,
+    pub report: PathBuf,
+}
+
+// the queue of a logical run without executing it
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Plan {
+    pub name: String,
+    // of all runs of the queue
+    pub passes: u64,
+    pub runs: Vec<PlannedRun>,
+}
+
+// <reports>/<name>_plan
+pub fn plan_dir(reports: &Path, plan: &Plan) -> PathBuf {
+    reports.join(format!("{}_plan", plan.name))
+}
+
+impl Plan {
+    // splits run into its queue like it would be executed
+    pub fn new(config: &CommonParams, run: RunParams) -> Result<Self> {
+        let name = run.name.clone();
+        let runs = run
+            .split_run_into_queue(&config.split)?
+            .iter()
+            .map(|r| {
+                Ok(PlannedRun {
+                    name: r.name.clone(),
+                    symbols: r.symbols.clone(),
+                    slice: r.slice,
+                    passes: r.indi_set.count_inputs_crossed()?,
+                    terminal_ini: to_terminal_config(config, r)?,
+                    params: r.to_param_string(),
+                    report: get_reports_full_path(config, r)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .context(format!("planning {} failed", name))?;
+        Ok(Plan {
+            name,
+            passes: runs
+                .iter()
+                .fold(0u64, |sum, r| sum.saturating_add(r.passes)),
+            runs,
+        })
+    }
+
+    // writes <dir>/<run>/terminal.ini and the .set file of every run
+    pub fn write(&self, dir: &Path, config: &CommonParams) -> Result<()> {
+        for run in &self.runs {
+            let run_dir = dir.join(&run.name);
+            fs::create_dir_all(&run_dir).context(format!("creating {:?} failed", run_dir))?;
+            fs::write(run_dir.join("terminal.ini"), &run.terminal_ini)?;
+            fs::write(run_dir.join(&config.params_file), &run.params)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::split_params::SplitStrategy;
+
+    #[test]
+    fn plan_test() {
+        let mut config = CommonParams::_new_test();
+        config.split = SplitParams {
+            strategy: SplitStrategy::Slices,
+            max_combinations: 7,
+            ..Default::default()
+        };
+        let mut run = RunParams::_new_test(1);
+        run.indi_set.get_mut(&IndiFunc::Confirm).unwrap().inputs =
+            _vec_vec_to_inputs(vec![vec![10., 20., 1.]]);
+
+        let plan = Plan::new(&config, run).unwrap();
+        assert_eq!(plan.name, "test");
+        assert_eq!(plan.passes, 11);
+        assert_eq!(plan.runs.len(), 2);
+        let first = &plan.runs[0];
+        assert_eq!(first.name, "test_0");
+        assert_eq!(first.slice, Some(0));
+        assert_eq!(first.passes, 5);
+        assert!(first
+            .terminal_ini
+            .ends_with("Symbol=USDCHF\nReport=reports\\test_0_USDCHF.xml"));
+        assert!(first
+            .params
+            .contains("Confirm_input0=0||10.00000||1.00000||14.00000||Y"));
+        assert_eq!(
+            first.report,
+            PathBuf::from(r"C:\workdir").join("reports/test_0_USDCHF.xml")
+        );
+
+        let dir = Path::new("/tmp/backtestd_plan_test");
+        let _ = fs::remove_dir_all(dir);
+        plan.write(dir, &config).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("test_1/expert_params.set")).unwrap(),
+            plan.runs[1].params
+        );
+        assert!(dir.join("test_1/terminal.ini").exists());
+    }
+}
@@ -0,0 +1,267 @@
+use super::report_header::ReportHeader;
+use super::ResultRow;
+use crate::params::indicator::Indicator;
+use crate::params::{IndiFunc, IndicatorSet, Input};
+
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// the metric the passes of an optimization are ranked by
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankMetric {
+    Custom,
+    ProfitFactor,
+    RecoveryFactor,
+    SharpeRatio,
+    Profit,
+}
+
+impl RankMetric {
+    pub fn value(&self, row: &ResultRow) -> f64 {
+        match self {
+            RankMetric::Custom => row.custom,
+            RankMetric::ProfitFactor => row.profit_factor as f64,
+            RankMetric::RecoveryFactor => row.recovery_factor as f64,
+            RankMetric::SharpeRatio => row.sharpe_ratio as f64,
+            RankMetric::Profit => row.profit as f64,
+        }
+    }
+}
+
+impl FromStr for RankMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "custom" => RankMetric::Custom,
+            "profit_factor" => RankMetric::ProfitFactor,
+            "recovery_factor" => RankMetric::RecoveryFactor,
+            "sharpe_ratio" => RankMetric::SharpeRatio,
+            "profit" => RankMetric::Profit,
+            _ => bail!("unknown metric: {}", s),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankParams {
+    pub metric: RankMetric,
+    // passes with fewer trades are not ranked
+    pub min_trades: u32,
+    pub top: usize,
+}
+
+impl Default for RankParams {
+    fn default() -> Self {
+        RankParams {
+            metric: RankMetric::Custom,
+            min_trades: 0,
+            top: 5,
+        }
+    }
+}
+
+// An indicator with the single values of a pass. It's a valid indicator config, the extra fields
+// are ignored when it's read as Indicator.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RankedIndicator {
+    #[serde(flatten)]
+    pub indicator: Indicator,
+    // the inputs of the optimization the pass was picked from
+    pub ranges: Vec<Input>,
+    pub pass: u64,
+    pub metric: RankMetric,
+    pub value: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BestPass {
+    pub rank: usize,
+    pub value: f64,
+    pub row: ResultRow,
+    // only the indicators with optimized inputs
+    pub indicators: HashMap<IndiFunc, RankedIndicator>,
+}
+
+// The rows with at least min_trades sorted by the metric, best first. Rows of different symbols
+// with the same inputs are only ranked once.
+pub fn rank_rows<'a>(rows: &'a [ResultRow], params: &RankParams) -> Vec<&'a ResultRow> {
+    let mut ranked = rows
+        .iter()
+        .filter(|r| r.trades >= params.min_trades)
+        .filter(|r| !params.metric.value(r).is_nan())
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| {
+        params
+            .metric
+            .value(b)
+            .partial_cmp(&params.metric.value(a))
+            .unwrap()
+    });
+    let mut seen = HashSet::<&Vec<BigDecimal>>::new();
+    ranked.retain(|r| seen.insert(&r.params));
+    ranked.truncate(params.top);
+    ranked
+}
+
+// base is the indicator set of the optimization the rows result from
+pub fn best_passes(
+    header: &ReportHeader,
+    rows: &[ResultRow],
+    base: &IndicatorSet,
+    params: &RankParams,
+) -> Result<Vec<BestPass>> {
+    rank_rows(rows, params)
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let set = header.indicator_set(base, &row.params)?;
+            let value = params.metric.value(row);
+            let indicators = header
+                .inputs
+                .iter()
+                .map(|c| c.func)
+                .map(|func| {
+                    (
+                        func,
+                        RankedIndicator {
+                            indicator: set[&func].clone(),
+                            ranges: base[&func].inputs.clone(),
+                            pass: row.pass,
+                            metric: params.metric,
+                            value,
+                        },
+                    )
+                })
+                .collect();
+            Ok(BestPass {
+                rank: i + 1,
+                value,
+                row: row.clone(),
+                indicators,
+            })
+        })
+        .collect()
+}
+
+// Write the indicators to <dir>/<func>/<name>_<rank>.yaml like the configs in config/indicator.
+// Existing files are only overwritten with force, otherwise nothing is written.
+pub fn write_best_passes(best: &[BestPass], dir: &Path, force: bool) -> Result<Vec<PathBuf>> {
+    let files = best
+        .iter()
+        .flat_map(|pass| {
+            pass.indicators.iter().map(move |(func, indi)| {
+                let file = dir
+                    .join(func.to_string().to_lowercase())
+                    .join(format!("{}_{}.yaml", indi.indicator.name, pass.rank));
+                (file, indi)
+            })
+        })
+        .collect::<Vec<_>>();
+    if !force {
+        if let Some((file, _)) = files.iter().find(|(file, _)| file.exists()) {
+            bail!("{:?} already exists, use --force to overwrite it", file);
+        }
+    }
+
+    for (file, indi) in &files {
+        fs::create_dir_all(file.parent().unwrap())?;
+        serde_any::to_file(file, indi)
+            .map_err(|e| anyhow!("writing {:?} failed: {:?}", file, e))?;
+    }
+    Ok(files.into_iter().map(|(file, _)| file).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::signal_class::SignalClass;
+    use crate::results::xml_reader::read_results_xml;
+
+    fn base() -> IndicatorSet {
+        [(
+            IndiFunc::Confirm,
+            Indicator {
+                name: "ama".to_string(),
+                filename: None,
+                shift: 0,
+                inputs: _vec_vec_to_inputs(vec![
+                    vec![10., 5., 15., 1.],
+                    vec![5., 2., 10., 1.],
+                    vec![1., 1., 3., 1.],
+                    vec![2., 1., 5., 1.],
+                    vec![6., 2., 10., 1.],
+                ]),
+                buffers: None,
+                params: None,
+                class: SignalClass::Preset,
+            },
+        )]
+        .iter()
+        .cloned()
+        .collect::<HashMap<_, _>>()
+        .into()
+    }
+
+    #[test]
+    fn rank_rows_test() {
+        let (_, rows) = read_results_xml(PathBuf::from("tests/multicurrency.xml")).unwrap();
+        let params = RankParams {
+            metric: RankMetric::ProfitFactor,
+            min_trades: 100,
+            top: 3,
+        };
+        let ranked = rank_rows(&rows, &params);
+        assert_eq!(ranked.len(), 3);
+        assert!(ranked.iter().all(|r| r.trades >= 100));
+        assert!(ranked[0].profit_factor >= ranked[1].profit_factor);
+        assert!(ranked[1].profit_factor >= ranked[2].profit_factor);
+        let best = rows
+            .iter()
+            .filter(|r| r.trades >= 100)
+            .map(|r| r.profit_factor)
+            .fold(f32::MIN, f32::max);
+        assert_eq!(ranked[0].profit_factor, best);
+
+        // duplicate inputs are ranked once
+        let dup = vec![rows[0].clone(), rows[0].clone()];
+        assert_eq!(rank_rows(&dup, &RankParams::default()).len(), 1);
+    }
+
+    #[test]
+    fn best_passes_test() {
+        let (header, rows) = read_results_xml(PathBuf::from("tests/multicurrency.xml")).unwrap();
+        let best = best_passes(&header, &rows, &base(), &RankParams::default()).unwrap();
+        assert_eq!(best.len(), 5);
+        assert_eq!(best[0].rank, 1);
+
+        let confirm = &best[0].indicators[&IndiFunc::Confirm];
+        assert_eq!(confirm.ranges, base()[&IndiFunc::Confirm].inputs);
+        assert_eq!(confirm.indicator.count_inputs_crossed().unwrap(), 1);
+        assert_eq!(
+            confirm.indicator.inputs[0],
+            Input::fixed(best[0].row.params[0].clone())
+        );
+
+        let dir = Path::new("/tmp/backtestd_best_test");
+        let _ = fs::remove_dir_all(dir);
+        let files = write_best_passes(&best, dir, false).unwrap();
+        assert_eq!(files.len(), 5);
+        assert_eq!(files[0], dir.join("confirm/ama_1.yaml"));
+
+        // existing configs are only overwritten with force
+        assert!(write_best_passes(&best, dir, false).is_err());
+        assert_eq!(write_best_passes(&best, dir, true).unwrap(), files);
+
+        // the written file is a valid indicator config
+        let indi = serde_any::from_file::<Indicator, _>(&files[0]).unwrap();
+        assert_eq!(indi, confirm.indicator);
+    }
+}
@@ -0,0 +1,102 @@
+use super::report_header::ReportHeader;
+use super::ResultRow;
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Number, Value};
+use std::path::Path;
+
+// read a result csv as written by read_results_xml_to_csv into one json object per row
+// keyed by the header. Numeric cells are returned as json numbers.
+pub fn read_csv_rows(csv_file: &Path) -> Result<Vec<Map<String, Value>>> {
+    let mut rdr =
+        csv::Reader::from_path(csv_file).context(format!("opening {:?} failed", csv_file))?;
+    let headers = rdr.headers()?.clone();
+
+    let mut rows = Vec::new();
+    for record in rdr.records() {
+        let record = record?;
+        rows.push(
+            headers
+                .iter()
+                .zip(record.iter())
+                .map(|(h, v)| (h.to_string(), cell_to_value(v)))
+                .collect(),
+        );
+    }
+    debug!("read {} rows from {:?}", rows.len(), csv_file);
+    Ok(rows)
+}
+
+// read a result csv or a merged csv into typed rows
+pub fn read_results_csv(csv_file: &Path) -> Result<(ReportHeader, Vec<ResultRow>)> {
+    let mut rdr =
+        csv::Reader::from_path(csv_file).context(format!("opening {:?} failed", csv_file))?;
+    let header = ReportHeader::parse(&rdr.headers()?.iter().collect::<Vec<_>>());
+
+    let mut rows = Vec::new();
+    for (i, record) in rdr.records().enumerate() {
+        let record = record?;
+        rows.push(
+            ResultRow::parse(&header, &record.iter().collect::<Vec<_>>()).context(format!(
+                "row {} of {:?}",
+                i + 1,
+                csv_file
+            ))?,
+        );
+    }
+    debug!("read {} rows from {:?}", rows.len(), csv_file);
+    Ok((header, rows))
+}
+
+pub fn cell_to_value(cell: &str) -> Value {
+    if let Ok(i) = cell.parse::<i64>() {
+        return Value::Number(i.into());
+    }
+    cell.parse::<f64>()
+        .ok()
+        .and_then(Number::from_f64)
+        .map(Value::Number)
+        .unwrap_or_else(|| Value::String(cell.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::results::xml_reader::{read_results_xml, read_results_xml_to_csv};
+    use std::path::PathBuf;
+
+    #[test]
+    fn read_csv_rows_test() {
+        let csv_file = Path::new("/tmp/multicurrency_rows.csv");
+        read_results_xml_to_csv(Path::new("tests/multicurrency.xml"), csv_file).unwrap();
+
+        let rows = read_csv_rows(csv_file).unwrap();
+        assert_eq!(rows.len(), 663);
+        assert_eq!(rows[0]["Pass"], Value::from(0));
+        assert_eq!(rows[0]["Trades"], Value::from(788));
+        assert_eq!(rows[0]["Profit"], Value::from(-4643.64));
+        assert_eq!(rows[0]["Confirm_double3"], Value::from(2));
+    }
+
+    #[test]
+    fn read_results_csv_test() {
+        let csv_file = Path::new("/tmp/multicurrency_results.csv");
+        read_results_xml_to_csv(Path::new("tests/multicurrency.xml"), csv_file).unwrap();
+
+        let (header, rows) = read_results_csv(csv_file).unwrap();
+        let (xml_header, xml_rows) =
+            read_results_xml(PathBuf::from("tests/multicurrency.xml")).unwrap();
+        assert_eq!(header, xml_header);
+        assert_eq!(rows, xml_rows);
+        assert_eq!(rows[0].trades, 788);
+        assert_eq!(rows[0].params.len(), 5);
+    }
+
+    #[test]
+    fn cell_to_value_test() {
+        assert_eq!(cell_to_value("12"), Value::from(12));
+        assert_eq!(cell_to_value("-0.5"), Value::from(-0.5));
+        assert_eq!(cell_to_value("abc"), Value::from("abc"));
+        assert_eq!(cell_to_value(""), Value::from(""));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::params::RunParams;
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+// Merges the csv files of the sub-runs created by split_run_into_queue into a single csv.
+// Each row is tagged with the Symbols and the slice of the sub-run it originates from.
+pub fn merge_csv_results(parts: &[(RunParams, PathBuf)], merged: &Path) -> Result<usize> {
+    let mut wtr =
+        csv::Writer::from_path(merged).context(format!("creating {:?} failed", merged))?;
+    let mut header: Option<csv::StringRecord> = None;
+    let mut count = 0;
+
+    for (run, csv_file) in parts {
+        let mut rdr =
+            csv::Reader::from_path(csv_file).context(format!("opening {:?} failed", csv_file))?;
+        let h = rdr.headers()?.clone();
+        if header.is_none() {
+            let mut merged_header = h.clone();
+            merged_header.push_field("Symbol");
+            merged_header.push_field("Slice");
+            wtr.write_record(&merged_header)?;
+            header = Some(h.clone());
+        }
+        ensure!(
+            header.as_ref() == Some(&h),
+            "header of {:?} does not match the previous results",
+            csv_file
+        );
+
+        let symbol = run.symbols.join(" ");
+        let slice = run.slice.map(|s| s.to_string()).unwrap_or_default();
+        for record in rdr.records() {
+            let mut record = record?;
+            record.push_field(&symbol);
+            record.push_field(&slice);
+            wtr.write_record(&record)?;
+            count += 1;
+        }
+    }
+    wtr.flush()?;
+
+    debug!(
+        "merged {} rows from {} runs into {:?}",
+        count,
+        parts.len(),
+        merged
+    );
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::results::xml_reader::read_results_xml_to_csv;
+
+    #[test]
+    fn merge_csv_results_test() {
+        let csv_file = PathBuf::from("/tmp/merge_part.csv");
+        read_results_xml_to_csv(Path::new("tests/multicurrency.xml"), &csv_file).unwrap();
+
+        let mut run0 = RunParams::_new_test(1);
+        run0.symbols = vec!["USDCHF".into()];
+        run0.slice = Some(0);
+        let mut run1 = run0.clone();
+        run1.symbols = vec!["AUDCAD".into()];
+        run1.slice = Some(1);
+
+        let merged = Path::new("/tmp/merge_merged.csv");
+        let count = merge_csv_results(
+            &[(run0, csv_file.clone()), (run1, csv_file.clone())],
+            merged,
+        )
+        .unwrap();
+        assert_eq!(count, 2 * 663);
+
+        let mut rdr = csv::Reader::from_path(merged).unwrap();
+        let header = rdr.headers().unwrap().clone();
+        assert_eq!(header.get(0), Some("Pass"));
+        assert_eq!(header.get(header.len() - 2), Some("Symbol"));
+        assert_eq!(header.get(header.len() - 1), Some("Slice"));
+
+        let records = rdr.records().map(|r| r.unwrap()).collect::<Vec<_>>();
+        assert_eq!(records.len(), 2 * 663);
+        assert_eq!(records[0].get(header.len() - 2), Some("USDCHF"));
+        assert_eq!(records[0].get(header.len() - 1), Some("0"));
+        assert_eq!(records[663].get(header.len() - 2), Some("AUDCAD"));
+        assert_eq!(records[663].get(header.len() - 1), Some("1"));
+    }
+}
@@ -1,19 +1,80 @@
+pub mod best;
+pub mod csv_reader;
+pub mod merge;
+pub mod query;
+pub mod report_header;
+pub mod robustness;
+pub mod sweep;
+pub mod tester_log;
 pub mod xml_reader;
 // pub mod csv_writer;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use report_header::{ReportHeader, METRIC_COLUMNS};
+use std::path::Path;
+
+// read the rows of a report xml or of a result csv
+pub fn read_results(file: &Path) -> Result<(ReportHeader, Vec<ResultRow>)> {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some("xml") => xml_reader::read_results_xml(file.to_path_buf()),
+        Some("csv") => csv_reader::read_results_csv(file),
+        _ => bail!("unknown results format: {:?}", file),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ResultRow {
-    pass: u64,
-    result: f64,
-    profit: f32,
-    expected_payoff: f32,
-    profit_factor: f32,
-    recovery_factor: f32,
-    sharpe_ratio: f32,
-    custom: f64,
-    equity_dd: f32,
-    trades: u32,
-    params: Vec<f32>,
+    pub pass: u64,
+    pub result: f64,
+    pub profit: f32,
+    pub expected_payoff: f32,
+    pub profit_factor: f32,
+    pub recovery_factor: f32,
+    pub sharpe_ratio: f32,
+    pub custom: f64,
+    pub equity_dd: f32,
+    pub trades: u32,
+    // values of the optimized inputs in the order of ReportHeader.inputs
+    pub params: Vec<BigDecimal>,
+}
+
+impl ResultRow {
+    pub fn parse<S: AsRef<str>>(header: &ReportHeader, cells: &[S]) -> Result<Self> {
+        ensure!(
+            cells.len() >= METRIC_COLUMNS,
+            "expected at least {} columns but got {}",
+            METRIC_COLUMNS,
+            cells.len()
+        );
+        let cell = |i: usize| cells[i].as_ref();
+        Ok(ResultRow {
+            pass: cell(0)
+                .parse()
+                .context(format!("Parsing Numeric 0 failed {:?}", cell(0)))?,
+            result: cell(1).parse().context("Parsing Numeric 1 failed")?,
+            profit: cell(2).parse().context("Parsing Numeric 2 failed")?,
+            expected_payoff: cell(3).parse().context("Parsing Numeric 3 failed")?,
+            profit_factor: cell(4).parse().context("Parsing Numeric 4 failed")?,
+            recovery_factor: cell(5).parse().context("Parsing Numeric 5 failed")?,
+            sharpe_ratio: cell(6).parse().context("Parsing Numeric 6 failed")?,
+            custom: cell(7).parse().context("Parsing Numeric 7 failed")?,
+            equity_dd: cell(8).parse().context("Parsing Numeric 8 failed")?,
+            trades: cell(9).parse().context("Parsing Numeric 9 failed")?,
+            params: header
+                .inputs
+                .iter()
+                .map(|c| {
+                    cells
+                        .get(c.column)
+                        .map(|v| v.as_ref())
+                        .unwrap_or_default()
+                        .parse()
+                        .context(format!("Parsing input {} failed", header.names[c.column]))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
 }
 
 // #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -0,0 +1,311 @@
+use super::csv_reader::cell_to_value;
+use super::xml_reader::XmlRows;
+
+use anyhow::{Context, Result};
+use derive_more::Display;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::path::Path;
+
+// the query doesn't fit the columns of the results
+#[derive(Debug, Display)]
+pub struct InvalidQuery(pub String);
+
+impl std::error::Error for InvalidQuery {}
+
+// a page of the rows of a report xml or a result csv. The column names are matched case
+// insensitively
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RowQuery {
+    // comma separated columns to return, all columns if empty
+    pub columns: String,
+    // comma separated conditions like trades>=50,profit>0. The operators are = != < <= > >=
+    pub filter: String,
+    // the column to sort by, descending if it starts with -
+    pub sort: String,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for RowQuery {
+    fn default() -> Self {
+        RowQuery {
+            columns: String::new(),
+            filter: String::new(),
+            sort: String::new(),
+            offset: 0,
+            limit: 100,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RowPage {
+    pub columns: Vec<String>,
+    // the rows that match the filter
+    pub total: usize,
+    pub offset: usize,
+    pub rows: Vec<Map<String, Value>>,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Condition {
+    column: usize,
+    op: Op,
+    value: String,
+}
+
+impl Condition {
+    fn matches(&self, row: &[String]) -> bool {
+        let ord = compare_cells(cell(row, self.column), &self.value);
+        match self.op {
+            Op::Eq => ord == Ordering::Equal,
+            Op::Ne => ord != Ordering::Equal,
+            Op::Lt => ord == Ordering::Less,
+            Op::Le => ord != Ordering::Greater,
+            Op::Gt => ord == Ordering::Greater,
+            Op::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+fn cell(row: &[String], column: usize) -> &str {
+    row.get(column).map(String::as_str).unwrap_or_default()
+}
+
+// numbers are compared by value, everything else as text
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+fn column(header: &[String], name: &str) -> Result<usize> {
+    let name = name.trim();
+    header
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(name))
+        .ok_or_else(|| InvalidQuery(format!("unknown column: {}", name)).into())
+}
+
+fn parse_condition(header: &[String], condition: &str) -> Result<Condition> {
+    // the two character operators first
+    let ops = [
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        ("!=", Op::Ne),
+        ("=", Op::Eq),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+    ];
+    let (pos, token, op) = ops
+        .iter()
+        .filter_map(|(token, op)| condition.find(token).map(|pos| (pos, token, *op)))
+        .min_by_key(|(pos, _, _)| *pos)
+        .ok_or_else(|| InvalidQuery(format!("no operator in {}", condition)))?;
+    Ok(Condition {
+        column: column(header, &condition[..pos])?,
+        op,
+        value: condition[pos + token.len()..].trim().to_string(),
+    })
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+// the rows of a csv or report xml, the first row is the header
+fn read_rows(file: &Path) -> Result<Box<dyn Iterator<Item = Result<Vec<String>>>>> {
+    Ok(match file.extension().and_then(|e| e.to_str()) {
+        Some("xml") => Box::new(XmlRows::open(file)?),
+        Some("csv") => Box::new(
+            csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_path(file)
+                .context(format!("opening {:?} failed", file))?
+                .into_records()
+                .map(|r| -> Result<Vec<String>> { Ok(r?.iter().map(String::from).collect()) }),
+        ),
+        _ => bail!("unknown results format: {:?}", file),
+    })
+}
+
+// Reads the rows of the file one by one and keeps only the rows of the page. If the rows are
+// sorted at most twice the rows up to the end of the page are kept.
+pub fn query_rows(file: &Path, query: &RowQuery) -> Result<RowPage> {
+    let mut rows = read_rows(file)?;
+    let header = rows
+        .next()
+        .context(format!("{:?} is empty", file))?
+        .context(format!("reading {:?} failed", file))?;
+
+    let mut columns = split_list(&query.columns)
+        .map(|c| column(&header, c))
+        .collect::<Result<Vec<_>>>()?;
+    if columns.is_empty() {
+        columns = (0..header.len()).collect();
+    }
+    let conditions = split_list(&query.filter)
+        .map(|c| parse_condition(&header, c))
+        .collect::<Result<Vec<_>>>()?;
+    let sort = match query.sort.trim() {
+        "" => None,
+        s if s.starts_with('-') => Some((column(&header, &s[1..])?, true)),
+        s => Some((column(&header, s)?, false)),
+    };
+    let compare = |a: &Vec<String>, b: &Vec<String>| match sort {
+        Some((c, desc)) => {
+            let ord = compare_cells(cell(a, c), cell(b, c));
+            if desc {
+                ord.reverse()
+            } else {
+                ord
+            }
+        }
+        None => Ordering::Equal,
+    };
+
+    let end = query.offset.saturating_add(query.limit);
+    let mut total = 0;
+    let mut kept = Vec::new();
+    for (i, row) in rows.enumerate() {
+        let row = row.context(format!("reading row {} of {:?} failed", i + 1, file))?;
+        if !conditions.iter().all(|c| c.matches(&row)) {
+            continue;
+        }
+        total += 1;
+        if sort.is_some() {
+            kept.push(row);
+            if kept.len() >= end.saturating_mul(2).max(1024) {
+                kept.sort_by(compare);
+                kept.truncate(end);
+            }
+        } else if total > query.offset && total <= end {
+            kept.push(row);
+        }
+    }
+    if sort.is_some() {
+        // stable so rows with the same value keep the order of the file
+        kept.sort_by(compare);
+        kept.truncate(end);
+        kept.drain(..query.offset.min(kept.len()));
+    }
+
+    Ok(RowPage {
+        columns: columns.iter().map(|c| header[*c].clone()).collect(),
+        total,
+        offset: query.offset,
+        rows: kept
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|c| (header[*c].clone(), cell_to_value(cell(row, *c))))
+                    .collect()
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::results::xml_reader::read_results_xml_to_csv;
+
+    const XML: &str = "tests/multicurrency.xml";
+
+    fn query(columns: &str, filter: &str, sort: &str, offset: usize, limit: usize) -> RowQuery {
+        RowQuery {
+            columns: columns.into(),
+            filter: filter.into(),
+            sort: sort.into(),
+            offset,
+            limit,
+        }
+    }
+
+    #[test]
+    fn query_rows_test() {
+        let page = query_rows(Path::new(XML), &Default::default()).unwrap();
+        assert_eq!(page.total, 663);
+        assert_eq!(page.rows.len(), 100);
+        assert_eq!(page.columns[0], "Pass");
+        assert_eq!(page.rows[0]["Trades"], Value::from(788));
+
+        let all = query_rows(Path::new(XML), &query("pass, TRADES", "", "", 0, 1000)).unwrap();
+        assert_eq!(all.rows.len(), 663);
+        let page = query_rows(Path::new(XML), &query("pass, TRADES", "", "", 660, 10)).unwrap();
+        assert_eq!(page.columns, vec!["Pass", "Trades"]);
+        assert_eq!(page.total, 663);
+        assert_eq!(page.rows[..], all.rows[660..]);
+        assert_eq!(page.rows[0].len(), 2);
+
+        let page = query_rows(
+            Path::new(XML),
+            &query(
+                "Pass,Trades,Profit",
+                "trades>=800,profit>0",
+                "-profit",
+                0,
+                5,
+            ),
+        )
+        .unwrap();
+        assert_eq!(page.total, 2);
+        let profits = page
+            .rows
+            .iter()
+            .map(|r| r["Profit"].as_f64().unwrap())
+            .collect::<Vec<_>>();
+        assert!(profits.windows(2).all(|w| w[0] >= w[1]));
+        assert!(page
+            .rows
+            .iter()
+            .all(|r| r["Trades"].as_i64().unwrap() >= 800));
+
+        // the pages of a sorted query are the sorted rows cut into pieces
+        let all = query_rows(Path::new(XML), &query("Pass", "", "Profit", 0, 1000)).unwrap();
+        let page = query_rows(Path::new(XML), &query("Pass", "", "Profit", 600, 50)).unwrap();
+        assert_eq!(page.rows[..], all.rows[600..650]);
+
+        // the csv gives the same rows
+        let csv = Path::new("/tmp/backtestd_query_rows_test.csv");
+        read_results_xml_to_csv(Path::new(XML), csv).unwrap();
+        let q = query("", "Trades<700", "Result", 1, 20);
+        assert_eq!(
+            query_rows(csv, &q).unwrap(),
+            query_rows(Path::new(XML), &q).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_query_test() {
+        let invalid = |q: RowQuery| {
+            let e = query_rows(Path::new(XML), &q).unwrap_err();
+            assert!(e.downcast_ref::<InvalidQuery>().is_some(), "{}", e);
+            e.to_string()
+        };
+        assert_eq!(
+            invalid(query("Pass,Nope", "", "", 0, 1)),
+            "unknown column: Nope"
+        );
+        assert_eq!(
+            invalid(query("", "trades", "", 0, 1)),
+            "no operator in trades"
+        );
+        assert_eq!(invalid(query("", "", "-foo", 0, 1)), "unknown column: foo");
+        assert!(query_rows(Path::new("tests/nothing.xml"), &Default::default()).is_err());
+    }
+}
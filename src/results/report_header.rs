@@ -0,0 +1,169 @@
+use crate::params::{IndiFunc, IndicatorSet, InputRange, InputValue};
+
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+
+// the metrics in the first columns of every report. The optimized inputs follow
+pub const METRIC_COLUMNS: usize = 10;
+
+// a column of the report holding an optimized input of an indicator
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct InputColumn {
+    pub column: usize,
+    pub func: IndiFunc,
+    // index into Indicator.inputs
+    pub input: usize,
+}
+
+impl InputColumn {
+    // the report names the inputs <func>_double<idx>, the .set file <func>_input<idx>
+    pub fn parse(column: usize, name: &str) -> Option<Self> {
+        let idx = name.find('_')?;
+        let func = name[..idx].parse().ok()?;
+        let rest = &name[idx + 1..];
+        let input = rest
+            .strip_prefix("double")
+            .or_else(|| rest.strip_prefix("input"))?
+            .parse()
+            .ok()?;
+        Some(InputColumn {
+            column,
+            func,
+            input,
+        })
+    }
+}
+
+// the header row of a report or of a result csv
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct ReportHeader {
+    pub names: Vec<String>,
+    pub inputs: Vec<InputColumn>,
+}
+
+impl ReportHeader {
+    // columns that are neither metrics nor inputs (like Symbol and Slice of a merged csv) are
+    // ignored
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Self {
+        ReportHeader {
+            names: names.iter().map(|n| n.as_ref().to_string()).collect(),
+            inputs: names
+                .iter()
+                .enumerate()
+                .skip(METRIC_COLUMNS)
+                .filter_map(|(i, n)| InputColumn::parse(i, n.as_ref()))
+                .collect(),
+        }
+    }
+
+    // Replaces the optimized inputs of the indicators in base with the single values of a pass.
+    // values are in the order of self.inputs. The result can be saved and run again.
+    pub fn indicator_set(
+        &self,
+        base: &IndicatorSet,
+        values: &[BigDecimal],
+    ) -> Result<IndicatorSet> {
+        ensure!(
+            values.len() == self.inputs.len(),
+            "expected {} input values but got {}",
+            self.inputs.len(),
+            values.len()
+        );
+        let mut set = base.clone();
+        for (col, value) in self.inputs.iter().zip(values) {
+            let indi = set.get_mut(&col.func).context(format!(
+                "column {} refers to {} which is not in the indicator set",
+                self.names[col.column], col.func
+            ))?;
+            let len = indi.inputs.len();
+            let input = indi.inputs.get_mut(col.input).context(format!(
+                "column {} refers to input {} but {} has {} inputs",
+                self.names[col.column], col.input, indi.name, len
+            ))?;
+            input.range = InputRange::Fixed(InputValue::Number(value.clone()));
+        }
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::indicator::Indicator;
+    use crate::params::signal_class::SignalClass;
+    use crate::params::{_vec_to_input, _vec_vec_to_inputs};
+    use crate::results::xml_reader::read_results_xml;
+    use std::path::PathBuf;
+
+    #[test]
+    fn input_column_test() {
+        assert_eq!(
+            InputColumn::parse(10, "Confirm_double3"),
+            Some(InputColumn {
+                column: 10,
+                func: IndiFunc::Confirm,
+                input: 3
+            })
+        );
+        assert_eq!(
+            InputColumn::parse(11, "Baseline_input0").map(|c| (c.func, c.input)),
+            Some((IndiFunc::Baseline, 0))
+        );
+        assert_eq!(InputColumn::parse(10, "Trades"), None);
+        assert_eq!(InputColumn::parse(10, "Symbol"), None);
+        assert_eq!(InputColumn::parse(10, "Confirm_buffer0"), None);
+        assert_eq!(InputColumn::parse(10, "Confirm_double"), None);
+    }
+
+    #[test]
+    fn indicator_set_test() {
+        let (header, rows) = read_results_xml(PathBuf::from("tests/multicurrency.xml")).unwrap();
+        assert_eq!(header.inputs.len(), 5);
+        assert_eq!(header.inputs[3].column, 13);
+        assert_eq!(header.names[9], "Trades");
+
+        let base: IndicatorSet = [(
+            IndiFunc::Confirm,
+            Indicator {
+                name: "ama".to_string(),
+                filename: None,
+                shift: 0,
+                inputs: _vec_vec_to_inputs(vec![
+                    vec![10., 5., 15., 1.],
+                    vec![5., 2., 10., 1.],
+                    vec![1., 1., 3., 1.],
+                    vec![2., 1., 5., 1.],
+                    vec![6., 2., 10., 1.],
+                    vec![3.],
+                ]),
+                buffers: None,
+                params: None,
+                class: SignalClass::Preset,
+            },
+        )]
+        .iter()
+        .cloned()
+        .collect::<std::collections::HashMap<_, _>>()
+        .into();
+
+        let set = header.indicator_set(&base, &rows[0].params).unwrap();
+        assert_eq!(
+            set[&IndiFunc::Confirm].inputs,
+            vec![
+                _vec_to_input(vec![11.]),
+                _vec_to_input(vec![8.]),
+                _vec_to_input(vec![1.]),
+                _vec_to_input(vec![2.]),
+                _vec_to_input(vec![6.]),
+                // not optimized
+                _vec_to_input(vec![3.]),
+            ]
+        );
+        assert_eq!(set[&IndiFunc::Confirm].count_inputs_crossed().unwrap(), 1);
+
+        assert!(header.indicator_set(&base, &rows[0].params[1..]).is_err());
+        assert!(header
+            .indicator_set(&IndicatorSet::default(), &rows[0].params)
+            .is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use super::best::RankMetric;
+use super::report_header::ReportHeader;
+use super::ResultRow;
+use crate::params::{IndicatorSet, Input};
+
+use anyhow::{Context, Result};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RobustnessParams {
+    pub metric: RankMetric,
+    // passes with fewer trades are treated as missing
+    pub min_trades: u32,
+    // number of grid steps in every input to consider as neighbours
+    pub radius: usize,
+    pub top: usize,
+}
+
+impl Default for RobustnessParams {
+    fn default() -> Self {
+        RobustnessParams {
+            metric: RankMetric::Custom,
+            min_trades: 0,
+            radius: 1,
+            top: 20,
+        }
+    }
+}
+
+// the score of a pass over its neighbourhood in the parameter grid
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RobustnessScore {
+    pub rank: usize,
+    pub pass: u64,
+    pub params: Vec<BigDecimal>,
+    // the metric of the pass itself
+    pub value: f64,
+    pub mean: f64,
+    pub worst: f64,
+    pub neighbours: usize,
+    // share of the neighbours in the grid that have a result
+    pub coverage: f64,
+    // lowest and highest value of every input in the neighbourhood
+    pub region: Vec<(BigDecimal, BigDecimal)>,
+}
+
+// The lowest value, step and number of values of an input column from its range in
+// Indicator.inputs. Descending ranges are turned around, so the index grows with the value.
+#[derive(Debug, PartialEq, Clone)]
+struct Axis {
+    start: BigDecimal,
+    step: BigDecimal,
+    len: i64,
+}
+
+impl Axis {
+    // the number of values is the exact count of the input, so the grid matches the slicing
+    fn from_input(input: &Input) -> Option<Self> {
+        let (start, _, step) = input.range.bounds()?;
+        let len = i64::try_from(input.count().ok()?).ok()?;
+        if *step < BigDecimal::zero() {
+            return Some(Axis {
+                start: start + step * BigDecimal::from(len - 1),
+                step: -step,
+                len,
+            });
+        }
+        Some(Axis {
+            start: start.clone(),
+            step: step.clone(),
+            len,
+        })
+    }
+
+    fn index(&self, value: &BigDecimal) -> i64 {
+        ((value - &self.start) / &self.step)
+            .to_f64()
+            .map_or(0, |i| i.round() as i64)
+    }
+}
+
+// a point of the grid with the rows of all symbols that share the inputs
+struct Point<'a> {
+    rows: Vec<&'a ResultRow>,
+    value: f64,
+}
+
+// Scores every pass by the mean and the worst metric of the passes within radius grid steps in
+// every input. The grid is taken from the ranges of the inputs in base. Rows of several symbols
+// with the same inputs are averaged. The scores are ranked by the mean, then the worst value and
+// the number of neighbours.
+pub fn score_robustness(
+    header: &ReportHeader,
+    rows: &[ResultRow],
+    base: &IndicatorSet,
+    params: &RobustnessParams,
+) -> Result<Vec<RobustnessScore>> {
+    let axes = header
+        .inputs
+        .iter()
+        .map(|c| {
+            base.get(&c.func)
+                .and_then(|indi| indi.inputs.get(c.input))
+                .and_then(Axis::from_input)
+                .context(format!(
+                    "column {} is not an optimized input of the indicator set",
+                    header.names[c.column]
+                ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut grid = HashMap::<Vec<i64>, Point>::new();
+    for row in rows {
+        let key = axes
+            .iter()
+            .zip(&row.params)
+            .map(|(a, v)| a.index(v))
+            .collect::<Vec<_>>();
+        grid.entry(key)
+            .or_insert_with(|| Point {
+                rows: Vec::new(),
+                value: 0.,
+            })
+            .rows
+            .push(row);
+    }
+    grid.retain(|_, p| {
+        p.rows.iter().map(|r| r.trades).sum::<u32>() >= params.min_trades
+            && p.rows.iter().all(|r| !params.metric.value(r).is_nan())
+    });
+    for p in grid.values_mut() {
+        p.value = p.rows.iter().map(|r| params.metric.value(r)).sum::<f64>() / p.rows.len() as f64;
+    }
+
+    let radius = params.radius as i64;
+    let mut keys = grid.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut scores = grid
+        .iter()
+        .map(|(key, point)| {
+            let mut values = Vec::new();
+            let mut region = point.rows[0]
+                .params
+                .iter()
+                .map(|v| (v.clone(), v.clone()))
+                .collect::<Vec<_>>();
+            for n in neighbours(&keys, key, radius) {
+                let p = &grid[n];
+                values.push(p.value);
+                for (r, v) in region.iter_mut().zip(&p.rows[0].params) {
+                    if *v < r.0 {
+                        r.0 = v.clone();
+                    }
+                    if *v > r.1 {
+                        r.1 = v.clone();
+                    }
+                }
+            }
+            // neighbours within the bounds of the grid
+            let expected = key
+                .iter()
+                .zip(&axes)
+                .map(|(k, a)| ((k + radius).min(a.len - 1) - (k - radius).max(0) + 1).max(1))
+                .product::<i64>();
+
+            RobustnessScore {
+                rank: 0,
+                pass: point.rows[0].pass,
+                params: point.rows[0].params.clone(),
+                value: point.value,
+                mean: values.iter().sum::<f64>() / values.len() as f64,
+                worst: values.iter().cloned().fold(f64::INFINITY, f64::min),
+                neighbours: values.len(),
+                coverage: values.len() as f64 / expected as f64,
+                region,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    scores.sort_by(|a, b| {
+        b.mean
+            .partial_cmp(&a.mean)
+            .unwrap()
+            .then(b.worst.partial_cmp(&a.worst).unwrap())
+            .then(b.neighbours.cmp(&a.neighbours))
+            .then(a.pass.cmp(&b.pass))
+    });
+    scores.truncate(params.top);
+    for (i, s) in scores.iter_mut().enumerate() {
+        s.rank = i + 1;
+    }
+    Ok(scores)
+}
+
+// The keys of the grid within radius steps of key in every input, including key itself. keys are
+// sorted, so only the keys close to key in the first input are compared. This stays fast with
+// many inputs, where the neighbourhood has far more points than the grid has results.
+fn neighbours<'k>(
+    keys: &'k [&'k Vec<i64>],
+    key: &'k [i64],
+    radius: i64,
+) -> impl Iterator<Item = &'k Vec<i64>> + 'k {
+    let (lo, hi) = match key.first() {
+        Some(first) => (
+            keys.partition_point(|k| k[0] < first - radius),
+            keys.partition_point(|k| k[0] <= first + radius),
+        ),
+        None => (0, keys.len()),
+    };
+    keys[lo..hi]
+        .iter()
+        .copied()
+        .filter(move |k| k.iter().zip(key).all(|(a, b)| (a - b).abs() <= radius))
+}
+
+pub fn write_robustness_csv(
+    header: &ReportHeader,
+    scores: &[RobustnessScore],
+    csv_file: &Path,
+) -> Result<()> {
+    let mut wtr =
+        csv::Writer::from_path(csv_file).context(format!("creating {:?} failed", csv_file))?;
+    let mut names = [
+        "Rank",
+        "Pass",
+        "Value",
+        "Mean",
+        "Worst",
+        "Neighbours",
+        "Coverage",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect::<Vec<_>>();
+    for c in &header.inputs {
+        let name = &header.names[c.column];
+        names.push(name.clone());
+        names.push(format!("{} Min", name));
+        names.push(format!("{} Max", name));
+    }
+    wtr.write_record(&names)?;
+
+    for s in scores {
+        let mut record = vec![
+            s.rank.to_string(),
+            s.pass.to_string(),
+            s.value.to_string(),
+            s.mean.to_string(),
+            s.worst.to_string(),
+            s.neighbours.to_string(),
+            s.coverage.to_string(),
+        ];
+        for (p, (min, max)) in s.params.iter().zip(&s.region) {
+            record.push(p.to_string());
+            record.push(min.to_string());
+            record.push(max.to_string());
+        }
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::indicator::Indicator;
+    use crate::params::signal_class::SignalClass;
+    use crate::params::{_vec_vec_to_inputs, IndiFunc};
+
+    fn row(pass: u64, custom: f64, params: &[f32]) -> ResultRow {
+        ResultRow {
+            pass,
+            result: custom,
+            profit: 0.,
+            expected_payoff: 0.,
+            profit_factor: 0.,
+            recovery_factor: 0.,
+            sharpe_ratio: 0.,
+            custom,
+            equity_dd: 0.,
+            trades: 100,
+            params: params.iter().map(|p| (*p).into()).collect(),
+        }
+    }
+
+    #[test]
+    fn score_robustness_test() {
+        let header = ReportHeader::parse(&[
+            "Pass",
+            "Result",
+            "Profit",
+            "Expected Payoff",
+            "Profit Factor",
+            "Recovery Factor",
+            "Sharpe Ratio",
+            "Custom",
+            "Equity DD %",
+            "Trades",
+            "Confirm_double0",
+            "Confirm_double1",
+        ]);
+        let base: IndicatorSet = [(
+            IndiFunc::Confirm,
+            Indicator {
+                name: "ama".to_string(),
+                filename: None,
+                shift: 0,
+                inputs: _vec_vec_to_inputs(vec![vec![1., 5., 1.], vec![10., 1., 3., 1.]]),
+                buffers: None,
+                params: None,
+                class: SignalClass::Preset,
+            },
+        )]
+        .iter()
+        .cloned()
+        .collect::<HashMap<_, _>>()
+        .into();
+
+        // a 5x3 grid with a spike at (5, 1) and a plateau around (2, 2)
+        let mut rows = Vec::new();
+        for a in 1..=5 {
+            for b in 1..=3 {
+                let custom = match (a, b) {
+                    (5, 1) => 30.,
+                    (1..=3, _) => 10.,
+                    _ => 0.,
+                };
+                rows.push(row(rows.len() as u64, custom, &[a as f32, b as f32]));
+            }
+        }
+
+        let scores = score_robustness(&header, &rows, &base, &RobustnessParams::default()).unwrap();
+        assert_eq!(scores.len(), 15);
+        assert_eq!(scores[0].rank, 1);
+        // the center of the plateau
+        assert_eq!(scores[0].params, vec![2.into(), 2.into()]);
+        assert_eq!(scores[0].mean, 10.);
+        assert_eq!(scores[0].worst, 10.);
+        assert_eq!(scores[0].neighbours, 9);
+        assert_eq!(scores[0].coverage, 1.);
+        assert_eq!(
+            scores[0].region,
+            vec![(1.into(), 3.into()), (1.into(), 3.into())]
+        );
+
+        // the spike is best by value but not by robustness
+        let spike = scores.iter().find(|s| s.value == 30.).unwrap();
+        assert!(spike.rank > 1);
+        assert_eq!(spike.worst, 0.);
+        assert_eq!(spike.neighbours, 4);
+
+        // missing neighbours reduce the coverage
+        let scores = score_robustness(
+            &header,
+            &rows[1..],
+            &base,
+            &RobustnessParams {
+                top: 100,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let corner = scores
+            .iter()
+            .find(|s| s.params == vec![1.into(), 2.into()])
+            .unwrap();
+        assert_eq!(corner.neighbours, 5);
+        assert_eq!(corner.coverage, 5. / 6.);
+
+        let csv_file = Path::new("/tmp/backtestd_robustness_test.csv");
+        write_robustness_csv(&header, &scores, csv_file).unwrap();
+        let mut rdr = csv::Reader::from_path(csv_file).unwrap();
+        assert_eq!(rdr.headers().unwrap().len(), 7 + 2 * 3);
+        assert_eq!(rdr.records().count(), 14);
+    }
+
+    #[test]
+    fn neighbours_test() {
+        let empty = vec![];
+        assert_eq!(neighbours(&[&empty], &empty, 1).count(), 1);
+
+        let grid = [
+            vec![0, 0, 0],
+            vec![0, 1, 5],
+            vec![1, 1, 1],
+            vec![2, 0, 0],
+            vec![3, 1, 1],
+        ];
+        let keys = grid.iter().collect::<Vec<_>>();
+        assert_eq!(
+            neighbours(&keys, &grid[2], 1).collect::<Vec<_>>(),
+            vec![&grid[0], &grid[2], &grid[3]]
+        );
+        assert_eq!(neighbours(&keys, &grid[1], 1).count(), 1);
+        assert_eq!(neighbours(&keys, &grid[0], 10).count(), 5);
+
+        // 40 inputs with a radius of 2 would be 5^40 offsets
+        let key = vec![1; 40];
+        assert_eq!(neighbours(&[&key], &key, 2).count(), 1);
+    }
+
+    #[test]
+    fn descending_axis_test() {
+        let input = _vec_vec_to_inputs(vec![vec![5., 1., -1.]]).remove(0);
+        let axis = Axis::from_input(&input).unwrap();
+        assert_eq!(
+            axis,
+            Axis {
+                start: 1.into(),
+                step: 1.into(),
+                len: 5
+            }
+        );
+        assert_eq!(axis.index(&5.into()), 4);
+
+        let input = _vec_vec_to_inputs(vec![vec![9., 9., 2., -2.]]).remove(0);
+        let axis = Axis::from_input(&input).unwrap();
+        // 9, 7, 5, 3
+        assert_eq!(axis.start, 3.into());
+        assert_eq!(axis.len, 4);
+
+        let zero = _vec_vec_to_inputs(vec![vec![1., 5., 0.]]).remove(0);
+        assert_eq!(Axis::from_input(&zero), None);
+        let wrong_way = _vec_vec_to_inputs(vec![vec![1., 5., -1.]]).remove(0);
+        assert_eq!(Axis::from_input(&wrong_way), None);
+    }
+}
@@ -0,0 +1,137 @@
+use super::csv_reader::read_results_csv;
+use crate::params::{IndiFunc, RunParams};
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+// the results of one indicator of a sweep
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SweepSummary {
+    pub rank: usize,
+    pub run: String,
+    pub indicator: String,
+    pub passes: usize,
+    // the best and the median Result of all passes
+    pub best: f64,
+    pub median: f64,
+}
+
+// Ranks the swept indicators of func by the best and then the median Result of their runs.
+// results holds the runs with their merged result csv.
+pub fn summarize_sweep(
+    func: IndiFunc,
+    results: &[(RunParams, PathBuf)],
+) -> Result<Vec<SweepSummary>> {
+    let mut summary = results
+        .iter()
+        .map(|(run, csv)| {
+            let (_, rows) = read_results_csv(csv)?;
+            let mut values = rows
+                .iter()
+                .map(|r| r.result)
+                .filter(|v| !v.is_nan())
+                .collect::<Vec<_>>();
+            ensure!(!values.is_empty(), "{} has no results", run.name);
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            Ok(SweepSummary {
+                rank: 0,
+                run: run.name.clone(),
+                indicator: run
+                    .indi_set
+                    .get(&func)
+                    .context(format!("{} has no {} indicator", run.name, func))?
+                    .name
+                    .clone(),
+                passes: values.len(),
+                best: values[values.len() - 1],
+                median: median(&values),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    summary.sort_by(|a, b| {
+        b.best
+            .partial_cmp(&a.best)
+            .unwrap()
+            .then(b.median.partial_cmp(&a.median).unwrap())
+    });
+    for (i, s) in summary.iter_mut().enumerate() {
+        s.rank = i + 1;
+    }
+    Ok(summary)
+}
+
+// values must be sorted
+fn median(values: &[f64]) -> f64 {
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        (values[mid - 1] + values[mid]) / 2.
+    }
+}
+
+pub fn write_sweep_summary(summary: &[SweepSummary], csv_file: &Path) -> Result<()> {
+    let mut wtr =
+        csv::Writer::from_path(csv_file).context(format!("creating {:?} failed", csv_file))?;
+    for s in summary {
+        wtr.serialize(s)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn summarize_sweep_test() {
+        let dir = Path::new("/tmp/backtestd_sweep_summary_test");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        let header = "Pass,Result,Profit,Expected Payoff,Profit Factor,Recovery Factor,\
+                      Sharpe Ratio,Custom,Equity DD %,Trades,Confirm_double0\n";
+        let results = [
+            ("steady", vec![4., 5., 6.]),
+            ("spiky", vec![0., 1., 6., 0.]),
+            ("weak", vec![1., 2.]),
+        ]
+        .iter()
+        .map(|(name, values)| {
+            let mut run = RunParams::_new_test(1);
+            run.name = format!("sweep_confirm_{}", name);
+            run.indi_set.get_mut(&IndiFunc::Confirm).unwrap().name = name.to_string();
+            let csv = dir.join(&run.name).with_extension("csv");
+            let mut content = header.to_string();
+            for (i, v) in values.iter().enumerate() {
+                content += &format!("{},{},0,0,0,0,0,0,0,10,{}\n", i, v, i);
+            }
+            fs::write(&csv, content).unwrap();
+            (run, csv)
+        })
+        .collect::<Vec<_>>();
+
+        let summary = summarize_sweep(IndiFunc::Confirm, &results).unwrap();
+        assert_eq!(
+            summary
+                .iter()
+                .map(|s| (s.rank, s.indicator.as_str(), s.best, s.median))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, "steady", 6., 5.),
+                (2, "spiky", 6., 0.5),
+                (3, "weak", 2., 1.5)
+            ]
+        );
+        assert_eq!(summary[1].passes, 4);
+        assert_eq!(summary[0].run, "sweep_confirm_steady");
+
+        let csv = dir.join("sweep_sweep.csv");
+        write_sweep_summary(&summary, &csv).unwrap();
+        let mut rdr = csv::Reader::from_path(&csv).unwrap();
+        assert_eq!(rdr.records().count(), 3);
+    }
+}
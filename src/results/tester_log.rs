@@ -0,0 +1,308 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, SeekFrom};
+use std::path::Path;
+
+// messages that make the results of a run worthless
+const FATAL_PATTERNS: &[&str] = &[
+    "cannot load",
+    "invalid input",
+    "incorrect input",
+    "wrong input",
+    "oninit returns non-zero",
+    "initialization failed",
+    "critical error",
+];
+
+// summary of the tester log Tester/logs/YYYYMMDD.log of a run
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct RunDiagnostics {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    // messages matching one of FATAL_PATTERNS
+    pub fatal: Vec<String>,
+    pub missing_history: Vec<String>,
+    // agents that connected to the tester
+    pub agents: Vec<String>,
+    // passes reported as finished by the agents
+    pub passes: u64,
+    // total passes reported at the end of the optimization
+    pub total_passes: Option<u64>,
+}
+
+impl RunDiagnostics {
+    pub fn is_fatal(&self) -> bool {
+        !self.fatal.is_empty()
+    }
+}
+
+// the tester log reported a fatal problem
+#[derive(Debug)]
+pub struct TesterFailed {
+    pub diagnostics: RunDiagnostics,
+}
+
+impl fmt::Display for TesterFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tester reported fatal errors:\n{}",
+            self.diagnostics.fatal.join("\n")
+        )
+    }
+}
+
+impl std::error::Error for TesterFailed {}
+
+// a single line of the tester log
+// <id>\t<level>\t<time>\t<source>\t<message>
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogLine<'a> {
+    pub level: u8,
+    pub time: &'a str,
+    pub source: &'a str,
+    pub message: &'a str,
+}
+
+impl<'a> LogLine<'a> {
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let mut cols = line.trim_end_matches('\r').splitn(5, '\t');
+        let _id = cols.next()?;
+        let level = cols.next()?.trim().parse().ok()?;
+        let time = cols.next()?;
+        let source = cols.next()?;
+        let message = cols.next()?;
+        Some(LogLine {
+            level,
+            time,
+            source,
+            message,
+        })
+    }
+}
+
+impl LogLine<'_> {
+    // an agent reported a finished pass of the optimization
+    pub fn is_finished_pass(&self) -> bool {
+        let msg = self.message.to_lowercase();
+        msg.starts_with("pass ") && msg.contains(" returned result")
+    }
+}
+
+impl fmt::Display for LogLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.time, self.source, self.message)
+    }
+}
+
+fn decode_utf16le(bytes: &[u8]) -> String {
+    let utf16 = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&utf16)
+}
+
+// MT5 writes the logs in UTF-16LE with a BOM
+pub fn decode_tester_log(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        decode_utf16le(&bytes[2..])
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+// Follows a tester log while the terminal writes it. Every call returns the lines that were
+// completed since the last call.
+#[derive(Debug, Default)]
+pub struct LogTail {
+    offset: u64,
+    utf16: bool,
+    // bytes of a line that isn't complete yet
+    pending: Vec<u8>,
+}
+
+impl LogTail {
+    pub fn read_lines(&mut self, log_file: &Path) -> Result<Vec<String>> {
+        let mut file = match File::open(log_file) {
+            Ok(file) => file,
+            // the terminal didn't write anything yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context(format!("opening {:?} failed", log_file)),
+        };
+        if file.metadata()?.len() < self.offset {
+            // a new log was started
+            *self = Default::default();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .context(format!("reading {:?} failed", log_file))?;
+        self.offset += bytes.len() as u64;
+        self.pending.extend(bytes);
+        if self.offset == self.pending.len() as u64 && self.pending.starts_with(&[0xFF, 0xFE]) {
+            self.utf16 = true;
+            self.pending.drain(..2);
+        }
+
+        let end = if self.utf16 {
+            (0..self.pending.len() / 2)
+                .rev()
+                .find(|i| self.pending[2 * i..2 * i + 2] == [b'\n', 0])
+                .map(|i| 2 * i + 2)
+        } else {
+            self.pending
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map(|i| i + 1)
+        };
+        let complete = match end {
+            Some(end) => self.pending.drain(..end).collect::<Vec<_>>(),
+            None => return Ok(Vec::new()),
+        };
+        let text = if self.utf16 {
+            decode_utf16le(&complete)
+        } else {
+            String::from_utf8_lossy(&complete).into_owned()
+        };
+        Ok(text
+            .lines()
+            .map(|l| l.trim_end_matches('\r'))
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect())
+    }
+}
+
+pub fn read_tester_log(log_file: &Path) -> Result<String> {
+    let bytes = fs::read(log_file).context(format!("reading {:?} failed", log_file))?;
+    Ok(decode_tester_log(&bytes))
+}
+
+pub fn parse_tester_log(log: &str) -> RunDiagnostics {
+    let mut diag = RunDiagnostics::default();
+    for line in log.lines().filter_map(LogLine::parse) {
+        let msg = line.message.to_lowercase();
+
+        if FATAL_PATTERNS.iter().any(|p| msg.contains(p)) {
+            diag.fatal.push(line.to_string());
+        }
+        if msg.contains("no history") || msg.contains("history not found") {
+            diag.missing_history.push(line.message.to_string());
+        }
+        match line.level {
+            0 => (),
+            1 => diag.warnings.push(line.to_string()),
+            _ => diag.errors.push(line.to_string()),
+        }
+
+        if msg.starts_with("connected") && !diag.agents.iter().any(|a| a == line.source) {
+            diag.agents.push(line.source.to_string());
+        }
+        if line.is_finished_pass() {
+            diag.passes += 1;
+        }
+        if let Some(total) = msg.split("total passes").nth(1) {
+            diag.total_passes = total
+                .trim_start_matches(|c: char| !c.is_ascii_digit())
+                .split(|c: char| !c.is_ascii_digit())
+                .next()
+                .and_then(|n| n.parse().ok());
+        }
+    }
+    diag
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOG: &str = "\
+KN\t0\t09:51:08.183\tTester\tEURUSD: history data begins from 2010.01.04 00:00
+DH\t0\t09:51:08.201\tCore 1\tconnecting to 127.0.0.1:3000
+EK\t0\t09:51:08.202\tCore 1\tconnected
+ML\t0\t09:51:08.203\tCore 2\tconnected
+GL\t0\t09:51:09.123\tCore 1\tpass 0 returned result 1234.00 in 0:00:01.234
+GQ\t0\t09:51:09.321\tCore 2\tpass 1 returned result -12.00 in 0:00:01.102
+OO\t1\t09:51:09.400\tCore 2\tAUDCAD: symbol synchronization is slow
+IE\t2\t09:51:10.000\tCore 1\t2017.08.01 00:00:00   cannot load custom indicator 'Aroon_Up_Down' [4802]
+PF\t2\t09:51:10.010\tTester\tGBPNZD: no history data from 2017.08.01 00:00 to 2019.08.20 00:00
+QR\t0\t09:55:00.000\tTester\toptimization finished, total passes 2
+";
+
+    #[test]
+    fn parse_tester_log_test() {
+        let diag = parse_tester_log(LOG);
+        assert_eq!(diag.passes, 2);
+        assert_eq!(diag.total_passes, Some(2));
+        assert_eq!(diag.agents, vec!["Core 1", "Core 2"]);
+        assert_eq!(diag.warnings.len(), 1);
+        assert_eq!(diag.errors.len(), 2);
+        assert_eq!(diag.missing_history.len(), 1);
+        assert!(diag.missing_history[0].starts_with("GBPNZD"));
+        assert_eq!(diag.fatal.len(), 1);
+        assert!(diag.fatal[0].contains("Aroon_Up_Down"));
+        assert!(diag.is_fatal());
+
+        // a symbol without history doesn't throw away the results of the others
+        let diag = parse_tester_log(LOG.lines().nth(8).unwrap());
+        assert_eq!(diag.missing_history.len(), 1);
+        assert!(!diag.is_fatal());
+
+        let diag = parse_tester_log(&LOG.lines().take(6).collect::<Vec<_>>().join("\n"));
+        assert!(!diag.is_fatal());
+        assert!(diag.errors.is_empty());
+        assert_eq!(diag.total_passes, None);
+
+        assert_eq!(parse_tester_log(""), RunDiagnostics::default());
+        assert_eq!(
+            parse_tester_log("garbage\nmore garbage"),
+            Default::default()
+        );
+    }
+
+    #[test]
+    fn decode_tester_log_test() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(
+            "KN\t0\tä"
+                .encode_utf16()
+                .flat_map(|c| c.to_le_bytes().to_vec()),
+        );
+        assert_eq!(decode_tester_log(&bytes), "KN\t0\tä");
+        assert_eq!(decode_tester_log("KN\t0".as_bytes()), "KN\t0");
+    }
+
+    #[test]
+    fn log_tail_test() {
+        let path = Path::new("/tmp/backtestd_log_tail_test.log");
+        let _ = fs::remove_file(path);
+        let mut tail = LogTail::default();
+        assert!(tail.read_lines(path).unwrap().is_empty());
+
+        let utf16 = |s: &str| {
+            s.encode_utf16()
+                .flat_map(|c| c.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>()
+        };
+        let mut file = File::create(path).unwrap();
+        file.write_all(&[0xFF, 0xFE]).unwrap();
+        let lines = LOG.lines().collect::<Vec<_>>();
+        file.write_all(&utf16(&format!("{}\r\n{}\r\nDH\t0", lines[0], lines[1])))
+            .unwrap();
+        assert_eq!(tail.read_lines(path).unwrap(), vec![lines[0], lines[1]]);
+        assert!(tail.read_lines(path).unwrap().is_empty());
+
+        // the rest of the line is written later
+        file.write_all(&utf16(&format!("{}\r\n", &lines[2][4..])))
+            .unwrap();
+        let line = tail.read_lines(path).unwrap();
+        assert_eq!(line, vec![lines[2].replacen("EK", "DH", 1)]);
+        assert!(LogLine::parse(&line[0]).is_some());
+
+        // a new log without a BOM
+        fs::write(path, "GL\t0\tx\n").unwrap();
+        assert_eq!(tail.read_lines(path).unwrap(), vec!["GL\t0\tx"]);
+    }
+}
@@ -1,54 +1,42 @@
+use super::report_header::ReportHeader;
 use super::ResultRow;
 use anyhow::{Context, Result};
 use quick_xml::events::Event;
 use quick_xml::Reader;
+use std::fs::File;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
 pub fn _read_results_xml(results_file: PathBuf) -> Result<Vec<ResultRow>> {
+    Ok(read_results_xml(results_file)?.1)
+}
+
+// read the rows of a report together with the header that maps the columns to the inputs
+pub fn read_results_xml(results_file: PathBuf) -> Result<(ReportHeader, Vec<ResultRow>)> {
     debug!("reading results from {:?}", results_file);
     let mut report_reader = Reader::from_file(results_file.as_path())?;
     report_reader.trim_text(true);
     let mut count = 0;
     let mut buf = Vec::new();
     let mut rows = Vec::new(); // may be larger as well
+    let mut header = ReportHeader::default();
     let mut txts = Vec::<String>::new();
     let mut txt: Option<String> = None;
 
     loop {
         match report_reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                match e.local_name() {
-                    b"Row" => {
-                        count += 1;
-                        txts.clear(); // delete the values but keep the capacity
-                                      // state = State::Row;
-                    }
-                    // b"Data" => state = State::Data,
-                    _ => (),
-                }
+            // b"Data" => state = State::Data,
+            Ok(Event::Start(ref e)) if e.local_name() == b"Row" => {
+                count += 1;
+                txts.clear(); // delete the values but keep the capacity
+                              // state = State::Row;
             }
             Ok(Event::End(ref e)) => match e.local_name() {
                 b"Row" => {
-                    if count > 1 {
-                        // ignore the header row
-                        rows.push(ResultRow {
-                            pass: txts[0]
-                                .parse()
-                                .context(format!("Parsing Numeric 0 failed {:?}", txts[0]))?,
-                            result: txts[1].parse().context("Parsing Numeric 1 failed")?,
-                            profit: txts[2].parse().context("Parsing Numeric 2 failed")?,
-                            expected_payoff: txts[3].parse().context("Parsing Numeric 3 failed")?,
-                            profit_factor: txts[4].parse().context("Parsing Numeric 4 failed")?,
-                            recovery_factor: txts[5].parse().context("Parsing Numeric 5 failed")?,
-                            sharpe_ratio: txts[6].parse().context("Parsing Numeric 6 failed")?,
-                            custom: txts[7].parse().context("Parsing Numeric 7 failed")?,
-                            equity_dd: txts[8].parse().context("Parsing Numeric 8 failed")?,
-                            trades: txts[9].parse().context("Parsing Numeric 9 failed")?,
-                            params: txts[10..]
-                                .iter()
-                                .map(|s| s.parse().expect("Parsing Numeric 10 input failed"))
-                                .collect(),
-                        });
+                    if count == 1 {
+                        header = ReportHeader::parse(&txts);
+                    } else {
+                        rows.push(ResultRow::parse(&header, &txts)?);
                     }
                 }
                 b"Data" => {
@@ -73,7 +61,7 @@ pub fn _read_results_xml(results_file: PathBuf) -> Result<Vec<ResultRow>> {
         count - 1 == rows.len(),
         "something went wrong with the row count"
     );
-    if rows.len() == 0 {
+    if rows.is_empty() {
         warn!(
             "read {} rows from {:?}",
             count - 1,
@@ -86,53 +74,63 @@ pub fn _read_results_xml(results_file: PathBuf) -> Result<Vec<ResultRow>> {
             results_file.file_name().unwrap()
         );
     }
-    Ok(rows)
+    Ok((header, rows))
 }
 
-pub fn read_results_xml_to_csv(xml_file: &Path, csv_file: &Path) -> Result<i32> {
-    let mut report_reader = Reader::from_file(xml_file)?;
-    report_reader.trim_text(true);
-    let mut csv_writer = csv::Writer::from_path(csv_file)?;
+// The cells of the rows of a report, the first row is the header. The report is read while
+// iterating.
+pub struct XmlRows {
+    reader: Reader<BufReader<File>>,
+    buf: Vec<u8>,
+}
 
-    let mut count = 0;
-    let mut buf = Vec::new();
-    let mut txts = Vec::<String>::new();
-    let mut txt: Option<String> = None;
+impl XmlRows {
+    pub fn open(xml_file: &Path) -> Result<Self> {
+        let mut reader =
+            Reader::from_file(xml_file).context(format!("opening {:?} failed", xml_file))?;
+        reader.trim_text(true);
+        Ok(XmlRows {
+            reader,
+            buf: Vec::new(),
+        })
+    }
 
-    loop {
-        match report_reader.read_event(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                match e.local_name() {
-                    b"Row" => {
-                        count += 1;
-                        txts.clear(); // delete the values but keep the capacity
-                                      // state = State::Row;
-                    }
-                    // b"Data" => state = State::Data,
-                    _ => (),
-                }
-            }
-            Ok(Event::End(ref e)) => match e.local_name() {
-                b"Row" => {
-                    csv_writer.write_record(&txts)?;
-                    // state = State::None;
-                }
-                b"Data" => {
-                    // state = State::Row;
-                    if let Some(t) = &txt {
-                        txts.push(t.into());
-                    } else {
-                        txts.push("".into());
-                    }
-                    txt = None;
+    fn next_row(&mut self) -> Result<Option<Vec<String>>> {
+        let mut cells = Vec::new();
+        let mut txt: Option<String> = None;
+        loop {
+            let mut row_end = false;
+            match self.reader.read_event(&mut self.buf)? {
+                Event::End(ref e) if e.local_name() == b"Row" => row_end = true,
+                Event::End(ref e) if e.local_name() == b"Data" => {
+                    cells.push(txt.take().unwrap_or_default())
                 }
+                Event::Text(e) => txt = Some(e.unescape_and_decode(&self.reader)?),
+                Event::Eof => return Ok(None),
                 _ => (),
-            },
-            Ok(Event::Text(e)) => txt = Some(e.unescape_and_decode(&report_reader)?),
-            Ok(Event::Eof) => break,
-            _ => (),
+            }
+            self.buf.clear();
+            if row_end {
+                return Ok(Some(cells));
+            }
         }
-        buf.clear();
+    }
+}
+
+impl Iterator for XmlRows {
+    type Item = Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().transpose()
+    }
+}
+
+pub fn read_results_xml_to_csv(xml_file: &Path, csv_file: &Path) -> Result<i32> {
+    let mut csv_writer = csv::Writer::from_path(csv_file)?;
+    let mut count = 0;
+    for row in XmlRows::open(xml_file)? {
+        csv_writer.write_record(&row?)?;
+        count += 1;
     }
 
     debug!(
@@ -157,11 +155,9 @@ mod xml_test {
     #[test]
     fn read_results_xml_to_csv_test() {
         let _ = fs::remove_file("/tmp/bt_run.csv");
-        let rows = read_results_xml_to_csv(
-            &Path::new("tests/bt_run.xml"),
-            &Path::new("/tmp/bt_run.csv"),
-        )
-        .unwrap();
+        let rows =
+            read_results_xml_to_csv(Path::new("tests/bt_run.xml"), Path::new("/tmp/bt_run.csv"))
+                .unwrap();
         assert_eq!(rows, 5714);
         assert!(Path::new("/tmp/bt_run.csv").exists());
     }
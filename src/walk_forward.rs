@@ -0,0 +1,299 @@
+use crate::params::*;
+use crate::results::best::{best_passes, BestPass, RankParams};
+use crate::results::csv_reader::read_results_csv;
+use crate::results::report_header::{ReportHeader, METRIC_COLUMNS};
+use crate::results::ResultRow;
+
+use anyhow::{Context, Result};
+use chrono::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// the in-sample optimization and the out-of-sample test of a single window
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WindowResult {
+    pub window: usize,
+    pub in_sample: (DateTime<Utc>, DateTime<Utc>),
+    pub out_of_sample: (DateTime<Utc>, DateTime<Utc>),
+    // the best pass of the in-sample optimization
+    pub best: BestPass,
+    // the test of the best pass on the out-of-sample period. One row per run of the queue
+    pub out_of_sample_rows: Vec<ResultRow>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WalkForwardReport {
+    pub name: String,
+    pub rank: RankParams,
+    pub header: ReportHeader,
+    pub windows: Vec<WindowResult>,
+    // summed over all out-of-sample tests
+    pub profit: f64,
+    pub trades: u64,
+}
+
+impl RunParams {
+    fn in_sample_run(&self, i: usize, window: &WalkForwardWindow) -> RunParams {
+        RunParams {
+            name: format!("{}_wf{}_is", self.name, i),
+            date: window.in_sample,
+            walk_forward: None,
+            ..self.clone()
+        }
+    }
+
+    // the optimized inputs are pinned to a single value but still flagged for optimization so
+    // the report lists them like the one of the in-sample run
+    fn out_of_sample_run(
+        &self,
+        i: usize,
+        window: &WalkForwardWindow,
+        header: &ReportHeader,
+        best: &BestPass,
+    ) -> Result<RunParams> {
+        let mut indi_set = header.indicator_set(&self.indi_set, &best.row.params)?;
+        for col in &header.inputs {
+            let step = match self.indi_set[&col.func].inputs[col.input].range.bounds() {
+                Some((_, _, step)) => step.clone(),
+                None => continue,
+            };
+            let input = &mut indi_set.get_mut(&col.func).unwrap().inputs[col.input];
+            if let InputRange::Fixed(InputValue::Number(v)) = &input.range {
+                input.range = InputRange::Range {
+                    start: v.clone(),
+                    stop: v.clone(),
+                    step,
+                };
+            }
+        }
+        Ok(RunParams {
+            name: format!("{}_wf{}_oos", self.name, i),
+            date: window.out_of_sample,
+            indi_set,
+            optimize: OptimizeMode::Complete,
+            walk_forward: None,
+            ..self.clone()
+        })
+    }
+}
+
+// Executes the walk-forward analysis of run. execute runs a single logical run and returns the
+// path to its merged result csv.
+pub fn run_walk_forward<F>(run: &RunParams, mut execute: F) -> Result<WalkForwardReport>
+where
+    F: FnMut(&RunParams) -> Result<PathBuf>,
+{
+    let wf = run
+        .walk_forward
+        .as_ref()
+        .context("the run has no walk-forward params")?;
+    let rank = RankParams {
+        top: 1,
+        ..wf.rank.clone()
+    };
+    let windows = wf.windows(&run.date)?;
+    info!(
+        "walk-forward of {} with {} windows",
+        run.name,
+        windows.len()
+    );
+
+    let mut report = WalkForwardReport {
+        name: run.name.clone(),
+        rank: rank.clone(),
+        header: ReportHeader::default(),
+        windows: Vec::with_capacity(windows.len()),
+        profit: 0.,
+        trades: 0,
+    };
+    for (i, window) in windows.iter().enumerate() {
+        let is_run = run.in_sample_run(i, window);
+        let (header, rows) = read_results_csv(&execute(&is_run)?)?;
+        let best = best_passes(&header, &rows, &run.indi_set, &rank)?
+            .pop()
+            .context(format!("no pass of {} qualifies as best", is_run.name))?;
+        debug!("best pass of {}: {:?}", is_run.name, best.row);
+
+        let oos_run = run.out_of_sample_run(i, window, &header, &best)?;
+        let (_, oos_rows) = read_results_csv(&execute(&oos_run)?)?;
+        ensure!(
+            !oos_rows.is_empty(),
+            "the out-of-sample test {} has no results",
+            oos_run.name
+        );
+
+        report.profit += oos_rows.iter().map(|r| r.profit as f64).sum::<f64>();
+        report.trades += oos_rows.iter().map(|r| r.trades as u64).sum::<u64>();
+        report.header = header;
+        report.windows.push(WindowResult {
+            window: i,
+            in_sample: window.in_sample,
+            out_of_sample: window.out_of_sample,
+            best,
+            out_of_sample_rows: oos_rows,
+        });
+    }
+    Ok(report)
+}
+
+// Writes the report to <dir>/<name>_walkforward.json and the stitched out-of-sample results to
+// <dir>/<name>_walkforward.csv
+pub fn write_walk_forward_report(report: &WalkForwardReport, dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    fs::write(
+        dir.join(format!("{}_walkforward.json", report.name)),
+        serde_json::to_string_pretty(report)?,
+    )?;
+
+    let csv_file = dir.join(format!("{}_walkforward.csv", report.name));
+    let mut wtr =
+        csv::Writer::from_path(&csv_file).context(format!("creating {:?} failed", csv_file))?;
+    let mut header = vec![
+        "Window".to_string(),
+        "In-Sample From".to_string(),
+        "In-Sample To".to_string(),
+        "Out-Of-Sample From".to_string(),
+        "Out-Of-Sample To".to_string(),
+        "In-Sample Pass".to_string(),
+        format!("In-Sample {:?}", report.rank.metric),
+    ];
+    header.extend(report.header.names.iter().take(METRIC_COLUMNS).cloned());
+    header.extend(
+        report
+            .header
+            .inputs
+            .iter()
+            .map(|c| report.header.names[c.column].clone()),
+    );
+    wtr.write_record(&header)?;
+
+    for w in &report.windows {
+        for row in &w.out_of_sample_rows {
+            let mut record = vec![
+                w.window.to_string(),
+                w.in_sample.0.to_rfc3339(),
+                w.in_sample.1.to_rfc3339(),
+                w.out_of_sample.0.to_rfc3339(),
+                w.out_of_sample.1.to_rfc3339(),
+                w.best.row.pass.to_string(),
+                w.best.value.to_string(),
+                row.pass.to_string(),
+                row.result.to_string(),
+                row.profit.to_string(),
+                row.expected_payoff.to_string(),
+                row.profit_factor.to_string(),
+                row.recovery_factor.to_string(),
+                row.sharpe_ratio.to_string(),
+                row.custom.to_string(),
+                row.equity_dd.to_string(),
+                row.trades.to_string(),
+            ];
+            record.extend(w.best.row.params.iter().map(|p| p.to_string()));
+            wtr.write_record(&record)?;
+        }
+    }
+    wtr.flush()?;
+    Ok(csv_file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::_vec_vec_to_inputs;
+    use crate::params::indicator::Indicator;
+    use crate::params::signal_class::SignalClass;
+    use crate::results::best::RankMetric;
+    use crate::results::xml_reader::read_results_xml_to_csv;
+    use std::collections::HashMap;
+
+    #[test]
+    fn run_walk_forward_test() {
+        let dir = Path::new("/tmp/backtestd_walk_forward_test");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        let mut run = RunParams::_new_test(0);
+        run.indi_set = [(
+            IndiFunc::Confirm,
+            Indicator {
+                name: "ama".to_string(),
+                filename: None,
+                shift: 0,
+                inputs: _vec_vec_to_inputs(vec![
+                    vec![10., 5., 15., 1.],
+                    vec![5., 2., 10., 1.],
+                    vec![1., 1., 3., 1.],
+                    vec![2., 1., 5., 1.],
+                    vec![6., 2., 10., 1.],
+                ]),
+                buffers: None,
+                params: None,
+                class: SignalClass::Preset,
+            },
+        )]
+        .iter()
+        .cloned()
+        .collect::<HashMap<_, _>>()
+        .into();
+        run.walk_forward = Some(WalkForwardParams {
+            in_sample: 365,
+            out_of_sample: 120,
+            step: None,
+            rank: RankParams {
+                metric: RankMetric::ProfitFactor,
+                min_trades: 100,
+                top: 10,
+            },
+        });
+
+        let mut executed = Vec::new();
+        let report = run_walk_forward(&run, |r| {
+            executed.push(r.clone());
+            let csv = dir.join(&r.name).with_extension("csv");
+            if r.name.ends_with("_is") {
+                read_results_xml_to_csv(Path::new("tests/multicurrency.xml"), &csv)?;
+            } else {
+                // the report of a single pass with the pinned inputs
+                let inputs = &r.indi_set[&IndiFunc::Confirm].inputs;
+                assert!(inputs.iter().all(|i| match &i.range {
+                    InputRange::Range { start, stop, .. } => start == stop,
+                    _ => false,
+                }));
+                fs::write(
+                    &csv,
+                    format!(
+                        "Pass,Result,Profit,Expected Payoff,Profit Factor,Recovery Factor,\
+                         Sharpe Ratio,Custom,Equity DD %,Trades,Confirm_double0,\
+                         Confirm_double1,Confirm_double2,Confirm_double3,Confirm_double4\n\
+                         0,1.5,100.5,1,1.2,1,0.1,1.5,5,120,{}\n",
+                        inputs
+                            .iter()
+                            .map(|i| i.range.bounds().unwrap().0.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    ),
+                )?;
+            }
+            Ok(csv)
+        })
+        .unwrap();
+
+        // 2 years: 365 + 120 + 120 + 120 < 730 < 365 + 4 * 120
+        assert_eq!(report.windows.len(), 3);
+        assert_eq!(executed.len(), 6);
+        assert_eq!(executed[0].name, "test_wf0_is");
+        assert_eq!(executed[1].name, "test_wf0_oos");
+        assert_eq!(executed[1].date, report.windows[0].out_of_sample);
+        assert_eq!(executed[1].optimize, OptimizeMode::Complete);
+        assert!(executed.iter().all(|r| r.walk_forward.is_none()));
+        assert_eq!(report.trades, 3 * 120);
+        assert!((report.profit - 3. * 100.5).abs() < 1e-6);
+        assert!(report.windows[0].best.row.trades >= 100);
+
+        let csv = write_walk_forward_report(&report, dir).unwrap();
+        let mut rdr = csv::Reader::from_path(&csv).unwrap();
+        assert_eq!(rdr.headers().unwrap().len(), 7 + 10 + 5);
+        assert_eq!(rdr.records().count(), 3);
+        assert!(dir.join("test_walkforward.json").exists());
+    }
+}
@@ -0,0 +1,203 @@
+// End-to-end tests of the backtestd binary with the fake terminal from src/bin/fake_terminal.rs
+// in place of terminal64.exe. Run with --features fake-terminal.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// prepares a workdir with the config, an indicator and a run file
+fn setup(name: &str, timeouts: &str) -> PathBuf {
+    let workdir = env::temp_dir().join(format!("backtestd_e2e_{}", name));
+    let _ = fs::remove_dir_all(&workdir);
+    fs::create_dir_all(workdir.join("MQL5/Profiles/Tester")).unwrap();
+
+    fs::write(
+        workdir.join("config.json"),
+        format!(
+            r#"{{
+  "params_file": "expert_params.set",
+  "terminal_exe": {terminal:?},
+  "workdir": {workdir:?},
+  "reports": "reports",
+  "expert": "backtestd\\backtestd-expert.ex5",
+  "wine": false,
+  "period": "D1",
+  "login": "1234",
+  "use_remote": false,
+  "use_local": true,
+  "replace_report": true,
+  "shutdown_terminal": true,
+  "deposit": 100000,
+  "currency": "USD",
+  "leverage": 100,
+  "execution_mode": 0,
+  "timeouts": {timeouts},
+  "journal": {journal:?}
+}}"#,
+            terminal = env!("CARGO_BIN_EXE_fake_terminal"),
+            workdir = workdir,
+            timeouts = timeouts,
+            journal = workdir.join("queue.jsonl"),
+        ),
+    )
+    .unwrap();
+
+    fs::write(
+        workdir.join("aroon.yaml"),
+        "---
+name: aroon
+filename: Aroon_Up_Down
+class: TwoLinesCross
+inputs:
+  - - 14.0
+    - 16.0
+    - 1.0
+buffers:
+  - 0
+  - 1
+shift: 0
+",
+    )
+    .unwrap();
+
+    fs::write(
+        workdir.join("run.yaml"),
+        format!(
+            "---
+name: e2e
+indi_set:
+  Confirm: {indi:?}
+date:
+  - 2017-08-01T00:00:00-00:00
+  - 2019-08-20T00:00:00-00:00
+backtest_model: 2
+optimize: 1
+optimize_crit: 0
+visual: false
+store_results: 0
+symbols:
+  - EURUSD
+  - AUDCAD
+",
+            indi = workdir.join("aroon.yaml"),
+        ),
+    )
+    .unwrap();
+
+    workdir
+}
+
+fn backtestd(workdir: &Path, mode: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_backtestd"))
+        .arg("--config")
+        .arg(workdir.join("config.json"))
+        .arg("run")
+        .arg(workdir.join("run.yaml"))
+        .env("FAKE_TERMINAL_MODE", mode)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn run_ok_test() {
+    let workdir = setup("ok", "{}");
+    let out = backtestd(&workdir, "ok");
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let merged = fs::read_to_string(workdir.join("reports/e2e.csv")).unwrap();
+    let mut lines = merged.lines();
+    let header = lines.next().unwrap();
+    assert!(header.starts_with("Pass,Result,Profit"));
+    assert!(header.contains("Confirm_double0"));
+    assert!(header.ends_with("Symbol,Slice"));
+    // 3 passes of the aroon period 14..16
+    assert_eq!(lines.count(), 3);
+
+    // the files the terminal was started with are kept next to the report
+    let ini = fs::read_to_string(workdir.join("reports/e2e_EURUSD.ini")).unwrap();
+    assert!(ini.contains("Report=reports\\e2e_EURUSD"));
+    assert!(fs::read_to_string(workdir.join("reports/e2e_EURUSD.set"))
+        .unwrap()
+        .contains("Confirm_"));
+}
+
+#[test]
+fn run_missing_indicator_test() {
+    let workdir = setup("missing_indicator", "{}");
+    let out = backtestd(&workdir, "missing_indicator");
+    assert!(!out.status.success());
+    assert!(!workdir.join("reports/e2e.csv").exists());
+}
+
+#[test]
+fn run_crash_test() {
+    let workdir = setup("crash", "{}");
+    let out = backtestd(&workdir, "crash");
+    assert!(!out.status.success());
+    assert!(!workdir.join("reports/e2e.csv").exists());
+}
+
+#[test]
+fn run_hang_timeout_test() {
+    let workdir = setup("hang", r#"{"run": 2, "retries": 1}"#);
+    let out = backtestd(&workdir, "hang");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("timeout"));
+}
+
+#[test]
+fn run_invalid_indicator_file_test() {
+    let workdir = setup("invalid_indicator_file", "{}");
+    fs::write(workdir.join("aroon.yaml"), "name: aroon\nshift: x\n").unwrap();
+    let out = backtestd(&workdir, "ok");
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Confirm"), "{}", stderr);
+    assert!(stderr.contains("aroon.yaml"), "{}", stderr);
+    assert!(stderr.contains("line 2"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn validate_test() {
+    let workdir = setup("validate", "{}");
+    let validate = |workdir: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_backtestd"))
+            .arg("--config")
+            .arg(workdir.join("config.json"))
+            .arg("validate")
+            .arg(workdir.join("run.yaml"))
+            .output()
+            .unwrap()
+    };
+    let out = validate(&workdir);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "e2e: ok\n");
+
+    let run = fs::read_to_string(workdir.join("run.yaml")).unwrap();
+    fs::write(
+        workdir.join("run.yaml"),
+        run.replace("visual: false", "visual: true")
+            .replace("  - EURUSD\n  - AUDCAD\n", "  []\n"),
+    )
+    .unwrap();
+    let out = validate(&workdir);
+    assert_eq!(out.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.starts_with("e2e: 2 problem(s) in the run"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("symbols: "));
+    assert!(stdout.contains("visual: "));
+
+    // nothing was started
+    let out = backtestd(&workdir, "ok");
+    assert_eq!(out.status.code(), Some(1));
+    assert!(!workdir.join("reports/e2e.csv").exists());
+}